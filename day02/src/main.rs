@@ -1,13 +1,107 @@
 use std::io;
 extern crate util;
 
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+enum Metric {
+    #[default]
+    Hamming,
+    Levenshtein,
+    /// Optimal string alignment: Levenshtein plus adjacent transpositions
+    Damerau,
+}
+
+impl Metric {
+    fn distance(self, a: &str, b: &str) -> Option<usize> {
+        let a = a.chars().collect::<Vec<char>>();
+        let b = b.chars().collect::<Vec<char>>();
+        match self {
+            Metric::Hamming => {
+                if a.len() != b.len() {
+                    return None;
+                }
+                Some(a.iter().zip(b.iter()).filter(|(x, y)| x != y).count())
+            }
+            Metric::Levenshtein => Some(edit_distance(&a, &b, false)),
+            Metric::Damerau => Some(edit_distance(&a, &b, true)),
+        }
+    }
+}
+
+fn edit_distance(a: &[char], b: &[char], transpose: bool) -> usize {
+    // Keep the last three rows of the DP table, which is all that the
+    // transposition check needs
+    let mut prev2 = vec![0; b.len() + 1];
+    let mut prev = (0..=b.len()).collect::<Vec<usize>>();
+    let mut cur = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        cur[0] = i;
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            cur[j] = (prev[j] + 1).min(cur[j - 1] + 1).min(prev[j - 1] + cost);
+            if transpose && i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                cur[j] = cur[j].min(prev2[j - 2] + 1);
+            }
+        }
+        std::mem::swap(&mut prev2, &mut prev);
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
+
+/// IDs linked by chains of IDs each within `threshold` of the next. IDs
+/// with no similar partner are left out.
+fn clusters(data: &[String], metric: Metric, threshold: usize) -> Vec<Vec<&str>> {
+    // Union-find over indices into `data`
+    fn find(parent: &mut [usize], i: usize) -> usize {
+        let mut root = i;
+        while parent[root] != root {
+            root = parent[root];
+        }
+        let mut i = i;
+        while parent[i] != root {
+            let next = parent[i];
+            parent[i] = root;
+            i = next;
+        }
+        root
+    }
+
+    let mut parent = (0..data.len()).collect::<Vec<usize>>();
+    for i in 0..data.len() {
+        for j in i + 1..data.len() {
+            match metric.distance(&data[i], &data[j]) {
+                Some(d) if d <= threshold => {
+                    let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+                    parent[a.max(b)] = a.min(b);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    let mut groups: Vec<Vec<&str>> = Vec::new();
+    let mut index: Vec<Option<usize>> = vec![None; data.len()];
+    for (i, id) in data.iter().enumerate() {
+        let root = find(&mut parent, i);
+        match index[root] {
+            Some(g) => groups[g].push(id),
+            None => {
+                index[root] = Some(groups.len());
+                groups.push(vec![id]);
+            }
+        }
+    }
+    groups.retain(|g| g.len() > 1);
+    groups
+}
+
 fn part1(data: &[String]) -> i64 {
     let mut twos = 0;
     let mut threes = 0;
     for word in data {
         let mut array = [0u8; 26];
         word.chars()
-            .for_each(|c| array[(c as usize - 'a' as usize)] += 1);
+            .for_each(|c| array[c as usize - 'a' as usize] += 1);
         twos += array
             .iter()
             .fold(0, |acc, &x| acc + if x == 2 { 1 } else { 0 })
@@ -23,11 +117,7 @@ fn part1(data: &[String]) -> i64 {
 fn part2(data: &[String]) -> Option<String> {
     for i in 0..data.len() {
         for j in i + 1..data.len() {
-            let x = data[i]
-                .chars()
-                .zip(data[j].chars())
-                .fold(0u8, |acc, (a, b)| acc + if a == b { 0 } else { 1 });
-            if x == 1 {
+            if Metric::default().distance(&data[i], &data[j]) == Some(1) {
                 return Some(
                     data[i]
                         .chars()
@@ -56,9 +146,56 @@ fn part2_test() {
     assert_eq!(part2(&data), Some(String::from("fgij")));
 }
 
+#[test]
+fn distance_test() {
+    assert_eq!(Metric::Hamming.distance("fghij", "fguij"), Some(1));
+    assert_eq!(Metric::Hamming.distance("fghij", "fghijk"), None);
+    assert_eq!(Metric::Levenshtein.distance("kitten", "sitting"), Some(3));
+    assert_eq!(Metric::Levenshtein.distance("abcd", "acbd"), Some(2));
+    assert_eq!(Metric::Damerau.distance("abcd", "acbd"), Some(1));
+}
+
+#[test]
+fn clusters_test() {
+    let data = util::read_lines("test2.txt").unwrap();
+    assert_eq!(
+        clusters(&data, Metric::Hamming, 1),
+        vec![vec!["fghij", "fguij"]]
+    );
+    let data = util::lines(&["abcde", "abxde", "abde", "zzzzz"]);
+    assert_eq!(
+        clusters(&data, Metric::Hamming, 1),
+        vec![vec!["abcde", "abxde"]]
+    );
+    assert_eq!(
+        clusters(&data, Metric::Levenshtein, 1),
+        vec![vec!["abcde", "abxde", "abde"]]
+    );
+}
+
 fn main() -> io::Result<()> {
     let data = util::read_lines("input.txt")?;
     println!("1: {:?}", part1(&data));
     println!("2: {:?}", part2(&data));
+
+    // Optional similarity mode: `day02 <hamming|levenshtein|damerau> [threshold]`
+    let mut args = std::env::args().skip(1);
+    if let Some(name) = args.next() {
+        let metric = match name.as_str() {
+            "hamming" => Metric::Hamming,
+            "levenshtein" => Metric::Levenshtein,
+            "damerau" => Metric::Damerau,
+            _ => return Err(io::Error::from(io::ErrorKind::InvalidInput)),
+        };
+        let threshold = match args.next() {
+            Some(t) => t
+                .parse::<usize>()
+                .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?,
+            None => 1,
+        };
+        for cluster in clusters(&data, metric, threshold) {
+            println!("{}", cluster.join(" "));
+        }
+    }
     Ok(())
 }
@@ -1,9 +1,12 @@
 extern crate util;
+use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::io;
 use std::str::FromStr;
 
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
 struct Claim {
+    id: u32,
     x: u32,
     y: u32,
     w: u32,
@@ -13,12 +16,30 @@ struct Claim {
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
 struct ParseClaimError;
 
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+enum FabricError {
+    InvalidClaim,
+    DuplicateId(u32),
+    TooLarge,
+}
+
+impl From<ParseClaimError> for FabricError {
+    fn from(_: ParseClaimError) -> FabricError {
+        FabricError::InvalidClaim
+    }
+}
+
 impl FromStr for Claim {
     type Err = ParseClaimError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut split = s.split_whitespace();
-        let _claim = split.next().ok_or(ParseClaimError)?;
+        let id = split
+            .next()
+            .ok_or(ParseClaimError)?
+            .trim_start_matches('#')
+            .parse::<u32>()
+            .map_err(|_| ParseClaimError)?;
         let _at = split.next().ok_or(ParseClaimError)?;
         let coords: Vec<u32> = split
             .next()
@@ -34,6 +55,7 @@ impl FromStr for Claim {
             .map(|s| s.parse::<u32>().map_err(|_| ParseClaimError))
             .collect::<Result<Vec<u32>, ParseClaimError>>()?;
         Ok(Claim {
+            id,
             x: coords[0],
             y: coords[1],
             w: dims[0],
@@ -47,6 +69,7 @@ fn claim_parse_test() {
     assert_eq!(
         "#123 @ 3,2: 5x4".parse::<Claim>(),
         Ok(Claim {
+            id: 123,
             x: 3,
             y: 2,
             w: 5,
@@ -55,56 +78,601 @@ fn claim_parse_test() {
     );
 }
 
-fn part1(data: &[String]) -> Result<usize, ParseClaimError> {
-    let claims = data
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Rect {
+    x0: u64,
+    y0: u64,
+    x1: u64,
+    y1: u64,
+}
+
+impl Rect {
+    fn new(x: u64, y: u64, w: u64, h: u64) -> Rect {
+        Rect {
+            x0: x,
+            y0: y,
+            x1: x.saturating_add(w),
+            y1: y.saturating_add(h),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.x0 >= self.x1 || self.y0 >= self.y1
+    }
+
+    fn intersects(&self, other: &Rect) -> bool {
+        !self.is_empty()
+            && !other.is_empty()
+            && self.x0 < other.x1
+            && other.x0 < self.x1
+            && self.y0 < other.y1
+            && other.y0 < self.y1
+    }
+
+    fn union(&self, other: &Rect) -> Rect {
+        Rect {
+            x0: self.x0.min(other.x0),
+            y0: self.y0.min(other.y0),
+            x1: self.x1.max(other.x1),
+            y1: self.y1.max(other.y1),
+        }
+    }
+}
+
+impl Claim {
+    fn rect(&self) -> Rect {
+        Rect::new(self.x as u64, self.y as u64, self.w as u64, self.h as u64)
+    }
+}
+
+// Tracks, per node, the length covered by at least one and by at least two
+// active claims
+struct CoverTree {
+    xs: Vec<u64>,
+    count: Vec<u32>,
+    once: Vec<u64>,
+    twice: Vec<u64>,
+}
+
+impl CoverTree {
+    fn new(xs: Vec<u64>) -> CoverTree {
+        let n = 4 * xs.len().max(1);
+        CoverTree {
+            xs,
+            count: vec![0; n],
+            once: vec![0; n],
+            twice: vec![0; n],
+        }
+    }
+
+    fn update(&mut self, lo: usize, hi: usize, delta: i32) {
+        if self.xs.len() > 1 {
+            self.update_node(1, 0, self.xs.len() - 1, lo, hi, delta);
+        }
+    }
+
+    fn update_node(&mut self, node: usize, l: usize, r: usize, lo: usize, hi: usize, delta: i32) {
+        if hi <= l || r <= lo {
+            return;
+        }
+        if lo <= l && r <= hi {
+            self.count[node] = (self.count[node] as i32 + delta) as u32;
+        } else {
+            let mid = (l + r) / 2;
+            self.update_node(2 * node, l, mid, lo, hi, delta);
+            self.update_node(2 * node + 1, mid, r, lo, hi, delta);
+        }
+        self.pull(node, l, r);
+    }
+
+    fn pull(&mut self, node: usize, l: usize, r: usize) {
+        let full = self.xs[r] - self.xs[l];
+        let leaf = r - l == 1;
+        let (c_once, c_twice) = if leaf {
+            (0, 0)
+        } else {
+            (
+                self.once[2 * node] + self.once[2 * node + 1],
+                self.twice[2 * node] + self.twice[2 * node + 1],
+            )
+        };
+        match self.count[node] {
+            0 => {
+                self.once[node] = c_once;
+                self.twice[node] = c_twice;
+            }
+            1 => {
+                self.once[node] = full;
+                self.twice[node] = c_once;
+            }
+            _ => {
+                self.once[node] = full;
+                self.twice[node] = full;
+            }
+        }
+    }
+
+    fn overlapped(&self) -> u64 {
+        self.twice[1]
+    }
+}
+
+// Sweep over y with a segment tree over compressed x, so the cost depends on
+// the number of claims rather than the fabric size
+fn overlap_area(claims: &[Claim]) -> u64 {
+    let mut xs = claims
         .iter()
-        .map(|x| x.parse::<Claim>())
-        .collect::<Result<Vec<Claim>, ParseClaimError>>()?;
-    // We know the whole fabric "is a very large sequare - at least 1000" on each side
-    let max_x = claims.iter().fold(0, |acc, &c| acc.max(c.x + c.w));
-    let max_y = claims.iter().fold(0, |acc, &c| acc.max(c.y + c.h));
-    let mut grid = (0..max_x * max_y).map(|_| 0).collect::<Vec<u8>>();
-    for c in &claims {
-        for y in c.y..c.y + c.h {
-            for x in c.x..c.x + c.w {
-                grid[((y * max_x) + x) as usize] += 1;
+        .flat_map(|c| vec![c.x as u64, c.x as u64 + c.w as u64])
+        .collect::<Vec<u64>>();
+    xs.sort_unstable();
+    xs.dedup();
+
+    // (y, delta, x0, x1): claims open at their top edge, close at the bottom
+    let mut events = claims
+        .iter()
+        .filter(|c| c.w > 0 && c.h > 0)
+        .flat_map(|c| {
+            let (x0, x1) = (c.x as u64, c.x as u64 + c.w as u64);
+            vec![
+                (c.y as u64, 1, x0, x1),
+                (c.y as u64 + c.h as u64, -1, x0, x1),
+            ]
+        })
+        .collect::<Vec<(u64, i32, u64, u64)>>();
+    events.sort_unstable();
+
+    let index = |x: u64| xs.binary_search(&x).unwrap_or_else(|i| i);
+    let mut tree = CoverTree::new(xs.clone());
+    let mut area = 0;
+    let mut last_y = 0;
+    for (y, delta, x0, x1) in events {
+        area += tree.overlapped() * (y - last_y);
+        tree.update(index(x0), index(x1), delta);
+        last_y = y;
+    }
+    area
+}
+
+#[test]
+fn overlap_area_test() {
+    let claims = [
+        Claim {
+            id: 1,
+            x: 0,
+            y: 0,
+            w: 3_000_000,
+            h: 2,
+        },
+        Claim {
+            id: 2,
+            x: 1_000_000,
+            y: 1,
+            w: 4_000_000,
+            h: 4_000_000,
+        },
+    ];
+    assert_eq!(overlap_area(&claims), 2_000_000);
+
+    // 300 identical claims would overflow a u8 counter
+    let stacked = (0..300)
+        .map(|id| Claim {
+            id,
+            x: 1,
+            y: 1,
+            w: 2,
+            h: 2,
+        })
+        .collect::<Vec<Claim>>();
+    assert_eq!(overlap_area(&stacked), 4);
+}
+
+const NODE_CAPACITY: usize = 8;
+
+#[derive(Debug)]
+struct Node {
+    bounds: Rect,
+    children: Vec<usize>,
+    leaf: bool,
+}
+
+// Static, bulk loaded with Sort-Tile-Recursive packing
+#[derive(Debug)]
+struct RTree {
+    nodes: Vec<Node>,
+    root: Option<usize>,
+}
+
+impl RTree {
+    fn new(rects: &[Rect]) -> RTree {
+        let mut tree = RTree {
+            nodes: Vec::new(),
+            root: None,
+        };
+        let mut level = rects
+            .iter()
+            .cloned()
+            .enumerate()
+            .filter(|(_, r)| !r.is_empty())
+            .map(|(i, r)| (r, i))
+            .collect::<Vec<(Rect, usize)>>();
+        if level.is_empty() {
+            return tree;
+        }
+        let mut leaf = true;
+        loop {
+            level = tree.pack(level, leaf);
+            leaf = false;
+            if level.len() == 1 {
+                tree.root = Some(level[0].1);
+                return tree;
+            }
+        }
+    }
+
+    fn pack(&mut self, mut items: Vec<(Rect, usize)>, leaf: bool) -> Vec<(Rect, usize)> {
+        let nodes = items.len().div_ceil(NODE_CAPACITY);
+        let slices = (nodes as f64).sqrt().ceil() as usize;
+        let per_slice = slices * NODE_CAPACITY;
+
+        items.sort_by_key(|(r, _)| r.x0 + r.x1);
+        let mut parents = Vec::with_capacity(nodes);
+        for slice in items.chunks_mut(per_slice) {
+            slice.sort_by_key(|(r, _)| r.y0 + r.y1);
+            for group in slice.chunks(NODE_CAPACITY) {
+                let bounds = group[1..]
+                    .iter()
+                    .fold(group[0].0, |acc, (r, _)| acc.union(r));
+                self.nodes.push(Node {
+                    bounds,
+                    children: group.iter().map(|&(_, i)| i).collect(),
+                    leaf,
+                });
+                parents.push((bounds, self.nodes.len() - 1));
+            }
+        }
+        parents
+    }
+
+    fn search(&self, rects: &[Rect], query: &Rect) -> Vec<usize> {
+        let mut found = Vec::new();
+        let mut stack = self.root.into_iter().collect::<Vec<usize>>();
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            if !node.bounds.intersects(query) {
+                continue;
+            }
+            if node.leaf {
+                found.extend(
+                    node.children
+                        .iter()
+                        .filter(|&&i| rects[i].intersects(query)),
+                );
+            } else {
+                stack.extend(&node.children);
             }
         }
+        found.sort_unstable();
+        found
     }
-    Ok(grid.iter().filter(|&x| *x > 1).count())
 }
 
-fn part2(data: &[String]) -> Result<Option<usize>, ParseClaimError> {
-    let claims = data
+#[derive(Debug)]
+struct Fabric {
+    claims: Vec<Claim>,
+    rects: Vec<Rect>,
+    ids: HashMap<u32, usize>,
+    tree: RTree,
+}
+
+impl Fabric {
+    fn new(claims: Vec<Claim>) -> Result<Fabric, FabricError> {
+        let mut ids = HashMap::new();
+        for (i, c) in claims.iter().enumerate() {
+            if ids.insert(c.id, i).is_some() {
+                return Err(FabricError::DuplicateId(c.id));
+            }
+        }
+        let rects = claims.iter().map(Claim::rect).collect::<Vec<Rect>>();
+        let tree = RTree::new(&rects);
+        Ok(Fabric {
+            claims,
+            rects,
+            ids,
+            tree,
+        })
+    }
+
+    fn touching(&self, x: u64, y: u64, w: u64, h: u64) -> Vec<u32> {
+        self.tree
+            .search(&self.rects, &Rect::new(x, y, w, h))
+            .into_iter()
+            .map(|i| self.claims[i].id)
+            .collect()
+    }
+
+    fn overlapping(&self, id: u32) -> Option<Vec<u32>> {
+        let rect = &self.rects[*self.ids.get(&id)?];
+        Some(
+            self.tree
+                .search(&self.rects, rect)
+                .into_iter()
+                .map(|i| self.claims[i].id)
+                .filter(|&i| i != id)
+                .collect(),
+        )
+    }
+
+    fn count_at(&self, x: u64, y: u64) -> usize {
+        self.touching(x, y, 1, 1).len()
+    }
+
+    fn overlap_area(&self) -> u64 {
+        overlap_area(&self.claims)
+    }
+
+    fn intact(&self) -> Vec<u32> {
+        self.claims
+            .iter()
+            .zip(self.rects.iter())
+            .filter(|(c, r)| !r.is_empty() && self.overlapping(c.id).is_some_and(|v| v.is_empty()))
+            .map(|(c, _)| c.id)
+            .collect()
+    }
+}
+
+#[test]
+fn fabric_query_test() {
+    let claims = util::read_lines("test1.txt")
+        .unwrap()
         .iter()
         .map(|x| x.parse::<Claim>())
-        .collect::<Result<Vec<Claim>, ParseClaimError>>()?;
-    // We know the whole fabric "is a very large sequare - at least 1000" on each side
-    let max_x = claims.iter().fold(0, |acc, &c| acc.max(c.x + c.w));
-    let max_y = claims.iter().fold(0, |acc, &c| acc.max(c.y + c.h));
-    let mut grid = (0..max_x * max_y).map(|_| 0).collect::<Vec<u8>>();
-    for c in &claims {
-        for y in c.y..c.y + c.h {
-            for x in c.x..c.x + c.w {
-                grid[((y * max_x) + x) as usize] += 1;
+        .collect::<Result<Vec<Claim>, ParseClaimError>>()
+        .unwrap();
+    let fabric = Fabric::new(claims).unwrap();
+    assert_eq!(fabric.overlapping(1), Some(vec![2]));
+    assert_eq!(fabric.overlapping(3), Some(vec![]));
+    assert_eq!(fabric.overlapping(4), None);
+    assert_eq!(fabric.count_at(3, 3), 2);
+    assert_eq!(fabric.count_at(5, 5), 1);
+    assert_eq!(fabric.count_at(0, 0), 0);
+    assert_eq!(fabric.touching(u64::MAX, 4, u64::MAX, 1), vec![]);
+    assert_eq!(fabric.touching(4, 4, 2, 2), vec![1, 2, 3]);
+    assert_eq!(fabric.intact(), vec![3]);
+
+    let grid = (0..40)
+        .flat_map(|y| {
+            (0..40).map(move |x| Claim {
+                id: y * 40 + x + 1,
+                x,
+                y,
+                w: 1,
+                h: 1,
+            })
+        })
+        .collect::<Vec<Claim>>();
+    let fabric = Fabric::new(grid).unwrap();
+    assert_eq!(fabric.touching(10, 10, 2, 1), vec![411, 412]);
+    assert_eq!(fabric.intact().len(), 1600);
+}
+
+#[test]
+fn duplicate_id_test() {
+    let data = util::lines(&["#1 @ 1,3: 4x4", "#7 @ 3,1: 4x4", "#1 @ 5,5: 2x2"]);
+    assert_eq!(part2(&data), Err(FabricError::DuplicateId(1)));
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    Ppm,
+    #[cfg(feature = "png")]
+    Png,
+}
+
+const MAX_RENDER_AREA: u64 = 1 << 24;
+
+fn coverage(fabric: &Fabric) -> Result<(usize, usize, Vec<u32>), FabricError> {
+    let width = fabric.rects.iter().fold(0, |acc, r| acc.max(r.x1));
+    let height = fabric.rects.iter().fold(0, |acc, r| acc.max(r.y1));
+    if width
+        .checked_mul(height)
+        .is_none_or(|area| area > MAX_RENDER_AREA)
+    {
+        return Err(FabricError::TooLarge);
+    }
+    let (width, height) = (width as usize, height as usize);
+    let mut grid = vec![0u32; width * height];
+    for r in fabric.rects.iter() {
+        for y in r.y0..r.y1 {
+            for x in r.x0..r.x1 {
+                grid[y as usize * width + x as usize] += 1;
             }
         }
     }
-    for (i, c) in claims.iter().enumerate() {
-        let mut m = true;
-        for y in c.y..c.y + c.h {
-            for x in c.x..c.x + c.w {
-                if !m {
-                    continue;
-                }
-                m = grid[((y * max_x) + x) as usize] == 1;
+    Ok((width, height, grid))
+}
+
+fn heat(count: u32, max: u32) -> [u8; 3] {
+    if count == 0 {
+        return [0, 0, 0];
+    }
+    let t = if max > 1 {
+        (count - 1) as f64 / (max - 1) as f64
+    } else {
+        0.0
+    };
+    [(255.0 * t) as u8, 32, (255.0 * (1.0 - t)) as u8]
+}
+
+fn render(fabric: &Fabric, format: Format) -> Result<Vec<u8>, FabricError> {
+    let (width, height, grid) = coverage(fabric)?;
+    let intact = fabric
+        .intact()
+        .iter()
+        .map(|id| fabric.rects[fabric.ids[id]])
+        .collect::<Vec<Rect>>();
+    let highlighted = |x: usize, y: usize| {
+        intact
+            .iter()
+            .any(|r| r.intersects(&Rect::new(x as u64, y as u64, 1, 1)))
+    };
+
+    if format == Format::Ascii {
+        // Singly claimed inches show the last digit of the claim ID,
+        // overlapping inches are marked with an X and intact claims with a #
+        let mut out = Vec::with_capacity((width + 1) * height);
+        for y in 0..height {
+            for x in 0..width {
+                out.push(match grid[y * width + x] {
+                    0 => b'.',
+                    1 if highlighted(x, y) => b'#',
+                    1 => {
+                        let id = fabric.touching(x as u64, y as u64, 1, 1)[0];
+                        b'0' + (id % 10) as u8
+                    }
+                    _ => b'X',
+                });
             }
+            out.push(b'\n');
         }
-        if m {
-            return Ok(Some(i + 1));
+        return Ok(out);
+    }
+
+    let max = grid.iter().cloned().max().unwrap_or(0);
+    let mut pixels = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            if highlighted(x, y) {
+                pixels.extend_from_slice(&[0, 255, 0]);
+            } else {
+                pixels.extend_from_slice(&heat(grid[y * width + x], max));
+            }
         }
     }
-    Ok(None)
+
+    Ok(match format {
+        #[cfg(feature = "png")]
+        Format::Png => png::encode(width as u32, height as u32, &pixels),
+        _ => util::ppm(width, height, &pixels),
+    })
+}
+
+// Stored (uncompressed) deflate blocks, so no compression library is needed
+#[cfg(feature = "png")]
+mod png {
+    fn crc32(data: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for &b in data {
+            crc ^= b as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 == 1 {
+                    (crc >> 1) ^ 0xEDB8_8320
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+        !crc
+    }
+
+    fn adler32(data: &[u8]) -> u32 {
+        let (mut a, mut b) = (1u32, 0u32);
+        for &x in data {
+            a = (a + x as u32) % 65521;
+            b = (b + a) % 65521;
+        }
+        (b << 16) | a
+    }
+
+    fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        let start = out.len();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(data);
+        let crc = crc32(&out[start..]);
+        out.extend_from_slice(&crc.to_be_bytes());
+    }
+
+    pub fn encode(width: u32, height: u32, rgb: &[u8]) -> Vec<u8> {
+        // Every scanline is prefixed with filter type 0 (None)
+        let mut raw = Vec::with_capacity(rgb.len() + height as usize);
+        for row in rgb.chunks(width.max(1) as usize * 3) {
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+
+        let mut zlib = vec![0x78, 0x01];
+        let blocks = raw.chunks(0xFFFF).collect::<Vec<&[u8]>>();
+        if blocks.is_empty() {
+            zlib.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+        }
+        for (i, block) in blocks.iter().enumerate() {
+            zlib.push((i + 1 == blocks.len()) as u8);
+            zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+            zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+            zlib.extend_from_slice(block);
+        }
+        zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&width.to_be_bytes());
+        header.extend_from_slice(&height.to_be_bytes());
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        let mut out = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        chunk(&mut out, b"IHDR", &header);
+        chunk(&mut out, b"IDAT", &zlib);
+        chunk(&mut out, b"IEND", &[]);
+        out
+    }
+
+    #[test]
+    fn crc32_test() {
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+    }
+}
+
+#[test]
+fn render_test() {
+    let fabric = parse_fabric(&util::read_lines("test1.txt").unwrap()).unwrap();
+    let ascii = String::from_utf8(render(&fabric, Format::Ascii).unwrap()).unwrap();
+    assert_eq!(
+        ascii,
+        "\
+.......
+...2222
+...2222
+.11XX22
+.11XX22
+.1111##
+.1111##
+"
+    );
+
+    let ppm = render(&fabric, Format::Ppm).unwrap();
+    assert!(ppm.starts_with(b"P6\n7 7\n255\n"));
+    assert_eq!(ppm.len(), 11 + 7 * 7 * 3);
+    assert_eq!(&ppm[11 + (5 * 7 + 5) * 3..][..3], &[0, 255, 0]);
+    assert_eq!(&ppm[11 + (3 * 7 + 3) * 3..][..3], &[255, 32, 0]);
+
+    let huge = parse_fabric(&["#1 @ 0,0: 1000000x1000000".to_string()]).unwrap();
+    assert_eq!(render(&huge, Format::Ppm), Err(FabricError::TooLarge));
+}
+
+fn parse_fabric(data: &[String]) -> Result<Fabric, FabricError> {
+    let claims = data
+        .iter()
+        .map(|x| x.parse::<Claim>())
+        .collect::<Result<Vec<Claim>, ParseClaimError>>()?;
+    Fabric::new(claims)
+}
+
+fn part1(data: &[String]) -> Result<u64, FabricError> {
+    Ok(parse_fabric(data)?.overlap_area())
+}
+
+fn part2(data: &[String]) -> Result<Vec<u32>, FabricError> {
+    Ok(parse_fabric(data)?.intact())
 }
 
 #[test]
@@ -116,12 +684,54 @@ fn part1_test() {
 #[test]
 fn part2_test() {
     let data = util::read_lines("test1.txt").unwrap();
-    assert_eq!(part2(&data), Ok(Some(3)));
+    assert_eq!(part2(&data), Ok(vec![3]));
 }
 
 fn main() -> io::Result<()> {
     let data = util::read_lines("input.txt")?;
     println!("Part 1: {:?}", part1(&data));
     println!("Part 2: {:?}", part2(&data));
+
+    // Optional queries: `day03 claim <id>`, `day03 point <x> <y>`,
+    // `day03 rect <x> <y> <w> <h>` or `day03 render <ascii|ppm|png> [path]`
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    if let Some((query, rest)) = args.split_first() {
+        let fabric =
+            parse_fabric(&data).map_err(|_| io::Error::from(io::ErrorKind::InvalidData))?;
+        if query == "render" {
+            let format = match rest.first().map(String::as_str) {
+                Some("ascii") => Format::Ascii,
+                Some("ppm") => Format::Ppm,
+                #[cfg(feature = "png")]
+                Some("png") => Format::Png,
+                _ => return Err(io::Error::from(io::ErrorKind::InvalidInput)),
+            };
+            let image =
+                render(&fabric, format).map_err(|_| io::Error::from(io::ErrorKind::InvalidData))?;
+            return match rest.get(1) {
+                Some(path) => std::fs::write(path, image),
+                None if format == Format::Ascii => {
+                    print!("{}", String::from_utf8_lossy(&image));
+                    Ok(())
+                }
+                None => Err(io::Error::from(io::ErrorKind::InvalidInput)),
+            };
+        }
+        let n = rest
+            .iter()
+            .map(|s| s.parse::<u64>())
+            .collect::<Result<Vec<u64>, _>>()
+            .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+        match (query.as_str(), n.as_slice()) {
+            ("claim", &[id]) => {
+                let id =
+                    u32::try_from(id).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+                println!("{:?}", fabric.overlapping(id))
+            }
+            ("point", &[x, y]) => println!("{}", fabric.count_at(x, y)),
+            ("rect", &[x, y, w, h]) => println!("{:?}", fabric.touching(x, y, w, h)),
+            _ => return Err(io::Error::from(io::ErrorKind::InvalidInput)),
+        }
+    }
     Ok(())
 }
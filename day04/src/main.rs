@@ -2,6 +2,8 @@ extern crate util;
 use self::ParseError::*;
 use std::collections::HashMap;
 use std::io;
+use std::ops::Range;
+use std::str::FromStr;
 
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Ord, Eq, Hash)]
 enum Action {
@@ -10,194 +12,929 @@ enum Action {
     Shift(Guard),
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Ord, Eq, Hash)]
+struct Timestamp {
+    year: i32,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Ord, Eq, Hash)]
 struct Event {
-    date: u16,
-    time: i16,
+    time: Timestamp,
     action: Action,
-    guard: Option<Guard>,
+    line: usize,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Ord, Eq, Hash)]
 struct Guard(u16);
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Window {
+    start: u16,
+    length: u16,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Shift {
+    guard: Guard,
+    start: Timestamp,
+    watch: Range<Timestamp>,
+    sleeps: Vec<Range<Timestamp>>,
+}
+
+/// Line numbers refer to the original, unsorted log
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Ord, Eq, Hash)]
-enum ParseError {
+enum LogError {
+    MissingShift { line: usize },
+    DoubleSleep { line: usize, previous: usize },
+    WakeBeforeSleep { line: usize },
+    UnterminatedSleep { line: usize },
+    OutsideWindow { line: usize },
+    DuplicateTimestamp { line: usize, previous: usize },
+}
+
+impl LogError {
+    fn line(&self) -> usize {
+        match *self {
+            LogError::MissingShift { line }
+            | LogError::DoubleSleep { line, .. }
+            | LogError::WakeBeforeSleep { line }
+            | LogError::UnterminatedSleep { line }
+            | LogError::OutsideWindow { line }
+            | LogError::DuplicateTimestamp { line, .. } => line,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq, Hash)]
+enum TimeError {
     Date,
     Time,
-    InvalidGuard,
-    InvalidAction,
 }
 
-fn date_wrap(date: u16) -> u16 {
-    // 1518 is a leap year
-    let days = [31, 29, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
-    let month = date / 100;
-    let day = date % 100;
-    if day > days[month as usize - 1] {
-        ((month + 1) % 12) * 100 + (day - days[month as usize - 1])
-    } else {
-        date
+#[derive(Debug, Clone, PartialEq, PartialOrd, Ord, Eq, Hash)]
+enum ParseError {
+    Date { line: usize },
+    Time { line: usize },
+    InvalidGuard { line: usize },
+    InvalidAction { line: usize },
+    Invalid(Vec<LogError>),
+}
+
+fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i32, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
     }
 }
 
-#[test]
-fn date_wrap_test() {
-    assert_eq!(date_wrap(1032), 1101);
-    assert_eq!(date_wrap(0229), 0229);
-    assert_eq!(date_wrap(1232), 0101);
-}
-
-fn parse_log(data: &[String]) -> Result<HashMap<Guard, [u16; 60]>, ParseError> {
-    let mut map: HashMap<u16, Vec<Event>> = HashMap::new();
-    let mut guards: HashMap<Guard, HashMap<u16, Vec<(i16, Action)>>> = HashMap::new();
-
-    for line in data {
-        let line = line
-            .trim_matches('[')
-            .split_whitespace()
-            .collect::<Vec<&str>>();
-        let mut date = line[0]
+impl Timestamp {
+    /// Days since 1970-01-01, using Howard Hinnant's `days_from_civil`
+    fn days(&self) -> i64 {
+        let y = self.year as i64 - if self.month <= 2 { 1 } else { 0 };
+        let era = y.div_euclid(400);
+        let yoe = y - era * 400;
+        let m = self.month as i64;
+        let doy = (153 * (m + if m > 2 { -3 } else { 9 }) + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146_097 + doe - 719_468
+    }
+
+    fn minutes(&self) -> i64 {
+        self.days() * 1440 + self.hour as i64 * 60 + self.minute as i64
+    }
+
+    fn from_minutes(minutes: i64) -> Timestamp {
+        let days = minutes.div_euclid(1440);
+        let rem = minutes.rem_euclid(1440);
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z - era * 146_097;
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        Timestamp {
+            year: year as i32,
+            month: month as u8,
+            day: day as u8,
+            hour: (rem / 60) as u8,
+            minute: (rem % 60) as u8,
+        }
+    }
+
+    fn add_minutes(self, minutes: i64) -> Timestamp {
+        Timestamp::from_minutes(self.minutes() + minutes)
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = TimeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim_matches(|c| c == '[' || c == ']');
+        let mut split = s.split_whitespace();
+        let date = split
+            .next()
+            .ok_or(TimeError::Date)?
             .split('-')
-            .skip(1)
-            .collect::<Vec<&str>>()
-            .join("")
-            .parse::<u16>()
-            .map_err(|_| Date)?;
-        let mut time = line[1]
-            .trim_matches(']')
+            .map(str::parse::<i32>)
+            .collect::<Result<Vec<i32>, _>>()
+            .map_err(|_| TimeError::Date)?;
+        let time = split
+            .next()
+            .ok_or(TimeError::Time)?
             .split(':')
-            .collect::<Vec<&str>>()
-            .join("")
-            .parse::<i16>()
-            .map_err(|_| Time)?;
-
-        if time >= 2300 {
-            // Should only occur for guard shifts beginning before midnight,
-            // we need to adjust the date the guard shift days (D-1) to the
-            // date on which sleeping/waking events happen
-            // Of course, we need to account for days > # of days in month
-            date = date_wrap(date + 1);
-            time -= 2360;
-        }
-        let action = match line[2] {
-            "wakes" => Action::Wake,
-            "falls" => Action::Sleep,
-            "Guard" => Action::Shift(Guard(
-                line[3]
+            .map(str::parse::<u8>)
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| TimeError::Time)?;
+        if date.len() != 3 || !(1..=12).contains(&date[1]) || date[2] < 1 {
+            return Err(TimeError::Date);
+        }
+        let (year, month) = (date[0], date[1] as u8);
+        if date[2] > days_in_month(year, month) as i32 {
+            return Err(TimeError::Date);
+        }
+        if time.len() != 2 || time[0] > 23 || time[1] > 59 {
+            return Err(TimeError::Time);
+        }
+        Ok(Timestamp {
+            year,
+            month,
+            day: date[2] as u8,
+            hour: time[0],
+            minute: time[1],
+        })
+    }
+}
+
+#[test]
+fn timestamp_test() {
+    let t = "[1518-02-28 23:58]".parse::<Timestamp>().unwrap();
+    assert_eq!(t.add_minutes(3), "1518-03-01 00:01".parse().unwrap());
+    let t = "[2000-02-28 23:58]".parse::<Timestamp>().unwrap();
+    assert_eq!(t.add_minutes(3), "2000-02-29 00:01".parse().unwrap());
+    let t = "[1518-12-31 23:59]".parse::<Timestamp>().unwrap();
+    assert_eq!(t.add_minutes(1), "1519-01-01 00:00".parse().unwrap());
+    assert_eq!(t.add_minutes(1).add_minutes(-1), t);
+    assert_eq!(
+        "1518-02-29 00:00".parse::<Timestamp>(),
+        Err(TimeError::Date)
+    );
+    assert_eq!(
+        "1900-02-29 00:00".parse::<Timestamp>(),
+        Err(TimeError::Date)
+    );
+    assert_eq!(
+        "1518-11-01 24:00".parse::<Timestamp>(),
+        Err(TimeError::Time)
+    );
+}
+
+impl Default for Window {
+    fn default() -> Window {
+        Window {
+            start: 0,
+            length: 60,
+        }
+    }
+}
+
+impl Window {
+    /// The first occurrence of this window that has not ended by `t`
+    fn occurrence(&self, t: Timestamp) -> Range<Timestamp> {
+        let midnight = Timestamp {
+            hour: 0,
+            minute: 0,
+            ..t
+        };
+        // Start from the previous day, for windows spanning midnight
+        let mut start = midnight.add_minutes(self.start as i64 - 1440);
+        while start.add_minutes(self.length as i64) <= t {
+            start = start.add_minutes(1440);
+        }
+        start..start.add_minutes(self.length as i64)
+    }
+
+    fn clock(&self, offset: usize) -> (usize, usize) {
+        let t = (self.start as usize + offset) % 1440;
+        (t / 60, t % 60)
+    }
+}
+
+impl FromStr for Window {
+    type Err = TimeError;
+
+    /// Parse `HH:MM-HH:MM`. Equal start and end times mean a full day.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let times = s
+            .split('-')
+            .map(|t| {
+                let hm = t
+                    .split(':')
+                    .map(str::parse::<u16>)
+                    .collect::<Result<Vec<u16>, _>>()
+                    .map_err(|_| TimeError::Time)?;
+                match hm.as_slice() {
+                    &[h, m] if h < 24 && m < 60 => Ok(h * 60 + m),
+                    _ => Err(TimeError::Time),
+                }
+            })
+            .collect::<Result<Vec<u16>, TimeError>>()?;
+        match times.as_slice() {
+            &[start, end] => Ok(Window {
+                start,
+                length: match (end + 1440 - start) % 1440 {
+                    0 => 1440,
+                    n => n,
+                },
+            }),
+            _ => Err(TimeError::Time),
+        }
+    }
+}
+
+#[test]
+fn window_test() {
+    let w = "22:00-06:00".parse::<Window>().unwrap();
+    assert_eq!(
+        w,
+        Window {
+            start: 1320,
+            length: 480
+        }
+    );
+    let t = "1518-03-01 01:30".parse::<Timestamp>().unwrap();
+    assert_eq!(
+        w.occurrence(t),
+        "1518-02-28 22:00".parse().unwrap().."1518-03-01 06:00".parse().unwrap()
+    );
+    let t = "1518-03-01 21:50".parse::<Timestamp>().unwrap();
+    assert_eq!(w.occurrence(t).start, "1518-03-01 22:00".parse().unwrap());
+    assert_eq!(w.clock(130), (0, 10));
+    assert_eq!("00:00-01:00".parse::<Window>(), Ok(Window::default()));
+
+    // Sleep spanning midnight only fits in the wider window
+    let data = util::lines(&[
+        "[1518-03-01 21:50] Guard #5 begins shift",
+        "[1518-03-01 23:50] falls asleep",
+        "[1518-03-02 00:20] wakes up",
+    ]);
+    let stats = parse_log(&data, w).unwrap();
+    let g5 = &stats.guards[&Guard(5)];
+    assert_eq!(g5.histogram.len(), 480);
+    assert_eq!(g5.total(), 30);
+    assert_eq!(g5.sleepiest_minute(Tie::First), Some(110));
+    assert_eq!(
+        parse_log(&data, Window::default()),
+        Err(Invalid(vec![LogError::OutsideWindow { line: 2 }]))
+    );
+}
+
+fn parse_events(data: &[String]) -> Result<Vec<Event>, ParseError> {
+    let mut events = Vec::with_capacity(data.len());
+    for (i, line) in data.iter().enumerate() {
+        let line_no = i + 1;
+        let close = line.find(']').ok_or(Date { line: line_no })?;
+        let time = line[..close].parse::<Timestamp>().map_err(|e| match e {
+            TimeError::Date => Date { line: line_no },
+            TimeError::Time => Time { line: line_no },
+        })?;
+        let words = line[close + 1..].split_whitespace().collect::<Vec<&str>>();
+        let action = match words.first() {
+            Some(&"wakes") => Action::Wake,
+            Some(&"falls") => Action::Sleep,
+            Some(&"Guard") => Action::Shift(Guard(
+                words
+                    .get(1)
+                    .ok_or(InvalidGuard { line: line_no })?
                     .trim_matches('#')
                     .parse::<u16>()
-                    .map_err(|_| InvalidGuard)?,
+                    .map_err(|_| InvalidGuard { line: line_no })?,
             )),
-            _ => return Err(InvalidAction),
+            _ => return Err(InvalidAction { line: line_no }),
         };
-
-        let event = Event {
-            date,
+        events.push(Event {
             time,
             action,
-            guard: None,
-        };
-        map.entry(date).or_insert_with(Vec::new).push(event);
+            line: line_no,
+        });
     }
+    events.sort();
+    Ok(events)
+}
 
-    // Check to make sure we only have 1 guard per day
-    for (_, v) in map.iter() {
-        let guard_id = v
-            .iter()
-            .filter_map(|ev| {
-                if let Action::Shift(Guard(t)) = ev.action {
-                    Some(t)
+/// Split a chronological event list into shifts, reporting every problem found
+fn parse_shifts(events: &[Event], window: Window) -> Result<Vec<Shift>, Vec<LogError>> {
+    let mut shifts: Vec<Shift> = Vec::new();
+    let mut errors = Vec::new();
+    // Time and line of the `falls asleep` event the guard has not woken from
+    let mut asleep: Option<(Timestamp, usize)> = None;
+    let mut previous: Option<&Event> = None;
+
+    for ev in events {
+        if let Some(prev) = previous {
+            if prev.time == ev.time {
+                errors.push(LogError::DuplicateTimestamp {
+                    line: ev.line,
+                    previous: prev.line,
+                });
+            }
+        }
+        previous = Some(ev);
+
+        // Guards fall asleep within the window, and wake up by its end
+        let inside = match (shifts.last(), ev.action) {
+            (Some(shift), Action::Sleep) => shift.watch.contains(&ev.time),
+            (Some(shift), Action::Wake) => {
+                shift.watch.start < ev.time && ev.time <= shift.watch.end
+            }
+            _ => true,
+        };
+        if !inside {
+            errors.push(LogError::OutsideWindow { line: ev.line });
+        }
+
+        match ev.action {
+            Action::Shift(guard) => {
+                if let Some((_, line)) = asleep.take() {
+                    errors.push(LogError::UnterminatedSleep { line });
+                }
+                shifts.push(Shift {
+                    guard,
+                    start: ev.time,
+                    watch: window.occurrence(ev.time),
+                    sleeps: Vec::new(),
+                });
+            }
+            Action::Sleep => {
+                if shifts.is_empty() {
+                    errors.push(LogError::MissingShift { line: ev.line });
+                } else if let Some((_, line)) = asleep {
+                    errors.push(LogError::DoubleSleep {
+                        line: ev.line,
+                        previous: line,
+                    });
                 } else {
-                    None
+                    asleep = Some((ev.time, ev.line));
                 }
+            }
+            Action::Wake => match shifts.last_mut() {
+                None => errors.push(LogError::MissingShift { line: ev.line }),
+                Some(shift) => match asleep.take() {
+                    Some((start, _)) => shift.sleeps.push(start..ev.time),
+                    None => errors.push(LogError::WakeBeforeSleep { line: ev.line }),
+                },
+            },
+        }
+    }
+    if let Some((_, line)) = asleep {
+        errors.push(LogError::UnterminatedSleep { line });
+    }
+
+    if errors.is_empty() {
+        Ok(shifts)
+    } else {
+        errors.sort_by_key(LogError::line);
+        Err(errors)
+    }
+}
+
+impl Shift {
+    /// The day on which the watched window begins. Shifts starting in the
+    /// evening are on duty for the following day's midnight hour.
+    fn date(&self) -> Timestamp {
+        Timestamp {
+            hour: 0,
+            minute: 0,
+            ..self.watch.start
+        }
+    }
+
+    fn asleep(&self) -> Vec<Range<usize>> {
+        let base = self.watch.start.minutes();
+        let len = self.watch.end.minutes() - base;
+        self.sleeps
+            .iter()
+            .map(|r| {
+                let start = (r.start.minutes() - base).max(0).min(len);
+                let end = (r.end.minutes() - base).max(0).min(len);
+                start as usize..end as usize
             })
-            .collect::<Vec<u16>>();
-        assert_eq!(guard_id.len(), 1);
-        let g = Guard(guard_id[0]);
-        v.iter().for_each(|ev| {
-            guards
-                .entry(g)
-                .or_insert_with(HashMap::new)
-                .entry(ev.date)
-                .or_insert_with(Vec::new)
-                .push((ev.time, ev.action));
-        });
+            .filter(|r| !r.is_empty())
+            .collect()
     }
 
-    let mut minutes: HashMap<Guard, [u16; 60]> = HashMap::new();
-    for (&g, v) in guards.iter_mut() {
-        for (_, ev) in v.iter_mut() {
-            ev.sort_by(|a, b| a.0.cmp(&b.0));
-            let mut mm = [0u16; 60];
-            for (time, stat) in ev {
-                match stat {
-                    Action::Sleep => {
-                        for t in *time..60 {
-                            mm[t as usize] += 1;
-                        }
-                    }
-                    Action::Wake => {
-                        for t in *time..60 {
-                            mm[t as usize] = 0;
-                        }
-                    }
-                    _ => (),
-                }
+    fn asleep_minutes(&self) -> Vec<bool> {
+        let len = (self.watch.end.minutes() - self.watch.start.minutes()) as usize;
+        let mut mm = vec![false; len];
+        for r in self.asleep() {
+            for m in r {
+                mm[m] = true;
             }
+        }
+        mm
+    }
+}
 
-            let clock = minutes.entry(g).or_insert([0u16; 60]);
-            for i in 0..60 {
-                clock[i] += mm[i];
-            }
+/// The original solution kept the last tied minute, so that is the default
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+enum Tie {
+    First,
+    #[default]
+    Last,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct GuardStats {
+    guard: Guard,
+    days: u32,
+    histogram: Vec<u32>,
+}
+
+impl GuardStats {
+    fn total(&self) -> u32 {
+        self.histogram.iter().sum()
+    }
+
+    fn max_count(&self) -> u32 {
+        self.histogram.iter().cloned().max().unwrap_or(0)
+    }
+
+    fn sleepiest_minutes(&self) -> Vec<usize> {
+        let max = self.max_count();
+        if max == 0 {
+            return Vec::new();
+        }
+        (0..self.histogram.len())
+            .filter(|&m| self.histogram[m] == max)
+            .collect()
+    }
+
+    fn sleepiest_minute(&self, tie: Tie) -> Option<usize> {
+        let minutes = self.sleepiest_minutes();
+        match tie {
+            Tie::First => minutes.first().cloned(),
+            Tie::Last => minutes.last().cloned(),
         }
     }
-    Ok(minutes)
+
+    fn probability(&self, minute: usize) -> f64 {
+        if self.days == 0 {
+            return 0.0;
+        }
+        self.histogram[minute] as f64 / self.days as f64
+    }
 }
 
-fn part1(data: &[String]) -> Result<usize, ParseError> {
-    let minutes = parse_log(data)?;
-
-    let mut highest = (0u16, Guard(0), 0usize);
-
-    for (&g, clock) in &minutes {
-        let xs = clock.iter().cloned().collect::<Vec<u16>>();
-        let total: u16 = xs.iter().cloned().sum();
-        let max = xs.iter().cloned().max().ok_or(InvalidAction)?;
-        let mut minute = 0;
-        for (i, &x) in xs.iter().enumerate() {
-            if x == max {
-                minute = i
+#[derive(Debug, Clone, PartialEq)]
+struct SleepStats {
+    guards: HashMap<Guard, GuardStats>,
+}
+
+impl SleepStats {
+    fn new(shifts: &[Shift], window: Window) -> SleepStats {
+        let len = window.length as usize;
+        // Accumulate interval endpoints, then prefix sum into the histogram
+        let mut deltas: HashMap<Guard, (u32, Vec<i64>)> = HashMap::new();
+        for shift in shifts {
+            let (days, delta) = deltas
+                .entry(shift.guard)
+                .or_insert_with(|| (0, vec![0; len + 1]));
+            *days += 1;
+            for r in shift.asleep() {
+                delta[r.start.min(len)] += 1;
+                delta[r.end.min(len)] -= 1;
             }
         }
-        if total > highest.0 {
-            highest = (total, g, minute);
-        }
+        let guards = deltas
+            .into_iter()
+            .map(|(guard, (days, delta))| {
+                let histogram = delta[..len]
+                    .iter()
+                    .scan(0, |acc, &d| {
+                        *acc += d;
+                        Some(*acc as u32)
+                    })
+                    .collect();
+                (
+                    guard,
+                    GuardStats {
+                        guard,
+                        days,
+                        histogram,
+                    },
+                )
+            })
+            .collect();
+        SleepStats { guards }
+    }
+
+    fn iter(&self) -> impl DoubleEndedIterator<Item = &GuardStats> {
+        let mut guards = self.guards.values().collect::<Vec<&GuardStats>>();
+        guards.sort_by_key(|g| g.guard);
+        guards.into_iter()
+    }
+
+    /// Ties go to the guard with the lowest ID
+    fn pick<K: Ord, F: Fn(&GuardStats) -> K>(&self, key: F) -> Option<&GuardStats> {
+        self.iter().rev().max_by_key(|g| key(g))
     }
+}
 
-    Ok(highest.2 * (highest.1).0 as usize)
+fn parse_log(data: &[String], window: Window) -> Result<SleepStats, ParseError> {
+    let shifts = parse_shifts(&parse_events(data)?, window).map_err(Invalid)?;
+    Ok(SleepStats::new(&shifts, window))
 }
 
-fn part2(data: &[String]) -> Result<usize, ParseError> {
-    let minutes = parse_log(data)?;
+fn answer(stats: &SleepStats, tie: Tie, strategy: impl Fn(&GuardStats) -> u32) -> usize {
+    stats
+        .pick(strategy)
+        .and_then(|g| Some(g.guard.0 as usize * g.sleepiest_minute(tie)?))
+        .unwrap_or(0)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ChartFormat {
+    Text,
+    Html,
+    Svg,
+}
+
+fn chart(shifts: &[Shift], window: Window, format: ChartFormat) -> String {
+    let stats = SleepStats::new(shifts, window);
+    // (guard, minute, times asleep on that minute, total minutes asleep)
+    let summary = stats
+        .iter()
+        .map(|g| {
+            let minute = g.sleepiest_minute(Tie::default()).unwrap_or(0);
+            (g.guard, minute, g.max_count(), g.total())
+        })
+        .collect::<Vec<ChartSummary>>();
+    let sleepiest = stats
+        .pick(GuardStats::max_count)
+        .and_then(|g| Some((g.guard, g.sleepiest_minute(Tie::default())?)));
+
+    let rows = shifts
+        .iter()
+        .map(|s| {
+            let date = s.date();
+            (
+                format!("{:02}-{:02}", date.month, date.day),
+                s.guard,
+                s.asleep_minutes(),
+            )
+        })
+        .collect::<Vec<ChartRow>>();
+
+    match format {
+        ChartFormat::Text => chart_text(window, &rows, &summary, sleepiest),
+        ChartFormat::Html => chart_html(window, &rows, &summary, sleepiest),
+        ChartFormat::Svg => chart_svg(window, &rows, &summary, sleepiest),
+    }
+}
+
+type ChartRow = (String, Guard, Vec<bool>);
+type ChartSummary = (Guard, usize, u32, u32);
+
+fn chart_text(
+    window: Window,
+    rows: &[ChartRow],
+    summary: &[ChartSummary],
+    sleepiest: Option<(Guard, usize)>,
+) -> String {
+    let width = rows
+        .iter()
+        .map(|r| format!("#{}", (r.1).0).len())
+        .max()
+        .unwrap_or(2)
+        .max(2);
+    let pad = 5 + 2 + width + 2;
+    let clock = (0..window.length as usize)
+        .map(|m| window.clock(m))
+        .collect::<Vec<(usize, usize)>>();
+    let mut out = format!("Date   {:w$}  Minute\n", "ID", w = width);
+    if window != Window::default() {
+        // Mark the start of each hour when the window is not just midnight
+        let mut hours = vec![b' '; clock.len() + 1];
+        for (i, &(h, m)) in clock.iter().enumerate() {
+            if i == 0 || m == 0 {
+                hours[i] = b'0' + (h / 10) as u8;
+                hours[i + 1] = b'0' + (h % 10) as u8;
+            }
+        }
+        out += &" ".repeat(pad);
+        out += String::from_utf8_lossy(&hours).trim_end();
+        out += "\n";
+    }
+    out += &" ".repeat(pad);
+    out += &clock
+        .iter()
+        .map(|&(_, m)| (b'0' + (m / 10) as u8) as char)
+        .collect::<String>();
+    out += "\n";
+    out += &" ".repeat(pad);
+    out += &clock
+        .iter()
+        .map(|&(_, m)| (b'0' + (m % 10) as u8) as char)
+        .collect::<String>();
+    out += "\n";
+    for (date, guard, asleep) in rows {
+        out += &format!("{}  {:w$}  ", date, format!("#{}", guard.0), w = width);
+        out += &asleep
+            .iter()
+            .map(|&a| if a { '#' } else { '.' })
+            .collect::<String>();
+        out += "\n";
+    }
+    if let Some((guard, minute)) = sleepiest {
+        out += &format!("{}^ #{}\n", " ".repeat(pad + minute), guard.0);
+    }
 
-    let mut highest = (0u16, Guard(0), 0usize);
+    out += &format!("\n{:w$}  Asleep  Sleepiest\n", "ID", w = width);
+    for (guard, minute, count, total) in summary {
+        out += &format!(
+            "{:w$}  {:<6}  {} ({}x)\n",
+            format!("#{}", guard.0),
+            total,
+            minute,
+            count,
+            w = width
+        );
+    }
+    out
+}
 
-    for (&g, clock) in &minutes {
-        let xs = clock.iter().cloned().collect::<Vec<u16>>();
-        let max = xs.iter().cloned().max().ok_or(InvalidAction)?;
-        let mut minute = 0;
-        for (i, &x) in xs.iter().enumerate() {
-            if x == max {
-                minute = i
+fn chart_html(
+    window: Window,
+    rows: &[ChartRow],
+    summary: &[ChartSummary],
+    sleepiest: Option<(Guard, usize)>,
+) -> String {
+    let column = sleepiest.map(|(_, m)| m);
+    let mut out = String::from(
+        "<table class=\"sleep\">\n\
+         <style>.sleep td{width:8px;height:12px;padding:0}\
+         .asleep{background:#333}.sleepiest{outline:1px solid red}</style>\n\
+         <tr><th>Date</th><th>ID</th>",
+    );
+    for m in 0..window.length as usize {
+        let (h, m) = window.clock(m);
+        out += &format!("<th title=\"{:02}:{:02}\">{:02}</th>", h, m, m);
+    }
+    out += "</tr>\n";
+    for (date, guard, asleep) in rows {
+        out += &format!("<tr><td>{}</td><td>#{}</td>", date, guard.0);
+        for (m, &a) in asleep.iter().enumerate() {
+            let mut class = Vec::new();
+            if a {
+                class.push("asleep");
+            }
+            if column == Some(m) {
+                class.push("sleepiest");
+            }
+            if class.is_empty() {
+                out += "<td></td>";
+            } else {
+                out += &format!("<td class=\"{}\"></td>", class.join(" "));
             }
         }
-        if max > highest.0 {
-            highest = (max, g, minute);
+        out += "</tr>\n";
+    }
+    out += "</table>\n<table class=\"summary\">\n\
+            <tr><th>ID</th><th>Asleep</th><th>Sleepiest</th><th>Times</th></tr>\n";
+    for (guard, minute, count, total) in summary {
+        out += &format!(
+            "<tr><td>#{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            guard.0, total, minute, count
+        );
+    }
+    out += "</table>\n";
+    out
+}
+
+fn chart_svg(
+    window: Window,
+    rows: &[ChartRow],
+    summary: &[ChartSummary],
+    sleepiest: Option<(Guard, usize)>,
+) -> String {
+    const LABEL: usize = 100;
+    const CELL: usize = 8;
+    const ROW: usize = 12;
+    let width = LABEL + window.length as usize * CELL;
+    let height = ROW * (rows.len() + summary.len() + 2);
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+         font-family=\"monospace\" font-size=\"10\">\n",
+        width, height
+    );
+    if let Some((_, minute)) = sleepiest {
+        out += &format!(
+            "<rect x=\"{}\" y=\"0\" width=\"{}\" height=\"{}\" fill=\"#fd0\" />\n",
+            LABEL + minute * CELL,
+            CELL,
+            ROW * rows.len()
+        );
+    }
+    for (i, (date, guard, asleep)) in rows.iter().enumerate() {
+        let y = i * ROW;
+        out += &format!(
+            "<text x=\"0\" y=\"{}\">{} #{}</text>\n",
+            y + ROW - 2,
+            date,
+            guard.0
+        );
+        for (m, &a) in asleep.iter().enumerate() {
+            if a {
+                out += &format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#333\" />\n",
+                    LABEL + m * CELL,
+                    y + 1,
+                    CELL,
+                    ROW - 2
+                );
+            }
         }
     }
+    for (i, (guard, minute, count, total)) in summary.iter().enumerate() {
+        out += &format!(
+            "<text x=\"0\" y=\"{}\">#{}: {} minutes asleep, most often at {} ({}x)</text>\n",
+            (rows.len() + 2 + i) * ROW - 2,
+            guard.0,
+            total,
+            minute,
+            count
+        );
+    }
+    out += "</svg>\n";
+    out
+}
+
+#[test]
+fn shift_attribution_test() {
+    // A shift beginning well before midnight, and one in a leap year
+    let data = util::lines(&[
+        "[2020-02-29 00:30] wakes up",
+        "[2020-02-28 21:15] Guard #7 begins shift",
+        "[2020-02-29 00:10] falls asleep",
+        "[2020-02-29 23:59] Guard #8 begins shift",
+        "[2020-03-01 00:05] falls asleep",
+        "[2020-03-01 00:06] wakes up",
+    ]);
+    let shifts = parse_shifts(&parse_events(&data).unwrap(), Window::default()).unwrap();
+    assert_eq!(shifts.len(), 2);
+    assert_eq!(shifts[0].guard, Guard(7));
+    assert_eq!(shifts[0].sleeps.len(), 1);
+    assert_eq!(shifts[1].guard, Guard(8));
+
+    let stats = parse_log(&data, Window::default()).unwrap();
+    assert_eq!(stats.guards[&Guard(7)].total(), 20);
+    assert_eq!(stats.guards[&Guard(8)].histogram[5], 1);
+    assert_eq!(shifts[0].date(), "2020-02-29 00:00".parse().unwrap());
+    assert_eq!(shifts[1].date(), "2020-03-01 00:00".parse().unwrap());
+}
+
+#[test]
+fn chart_test() {
+    let data = util::read_lines("test1.txt").unwrap();
+    let shifts = parse_shifts(&parse_events(&data).unwrap(), Window::default()).unwrap();
+    let text = chart(&shifts, Window::default(), ChartFormat::Text);
+    let expected = "\
+Date   ID   Minute
+            000000000011111111112222222222333333333344444444445555555555
+            012345678901234567890123456789012345678901234567890123456789
+11-01  #10  .....####################.....#########################.....
+11-02  #99  ........................................##########..........
+11-03  #10  ........................#####...............................
+11-04  #99  ....................................##########..............
+11-05  #99  .............................................##########.....
+                                                         ^ #99
 
-    Ok(highest.2 * (highest.1).0 as usize)
+ID   Asleep  Sleepiest
+#10  50      24 (2x)
+#99  30      45 (3x)
+";
+    assert_eq!(text, expected);
+    assert!(chart(&shifts, Window::default(), ChartFormat::Html)
+        .contains("<td class=\"asleep sleepiest\">"));
+    assert!(chart(&shifts, Window::default(), ChartFormat::Svg).starts_with("<svg"));
+}
+
+#[test]
+fn validate_test() {
+    let data = util::lines(&[
+        "[1518-11-01 00:05] falls asleep",
+        "[1518-11-01 00:00] Guard #10 begins shift",
+        "[1518-11-01 00:05] falls asleep",
+        "[1518-11-01 00:25] wakes up",
+        "[1518-11-01 00:30] wakes up",
+        "[1518-11-01 23:58] Guard #99 begins shift",
+        "[1518-11-01 23:59] falls asleep",
+        "[1518-11-02 00:10] falls asleep",
+    ]);
+    assert_eq!(
+        parse_shifts(&parse_events(&data).unwrap(), Window::default()),
+        Err(vec![
+            LogError::DuplicateTimestamp {
+                line: 3,
+                previous: 1
+            },
+            LogError::DoubleSleep {
+                line: 3,
+                previous: 1
+            },
+            LogError::WakeBeforeSleep { line: 5 },
+            LogError::OutsideWindow { line: 7 },
+            LogError::UnterminatedSleep { line: 7 },
+            LogError::DoubleSleep {
+                line: 8,
+                previous: 7
+            },
+        ])
+    );
+
+    let data = vec![String::from("[1518-11-01 00:05] falls asleep")];
+    assert_eq!(
+        part1(&data),
+        Err(Invalid(vec![LogError::MissingShift { line: 1 }]))
+    );
+
+    let mut data = util::read_lines("test1.txt").unwrap();
+    data[2] = String::from("[1518-11-31 00:05] falls asleep");
+    assert_eq!(part1(&data), Err(Date { line: 3 }));
+    data[2] = String::from("[1518-11-01 00:05] dozes off");
+    assert_eq!(part1(&data), Err(InvalidAction { line: 3 }));
+}
+
+#[test]
+fn sleep_stats_test() {
+    let data = util::read_lines("test1.txt").unwrap();
+    let stats = parse_log(&data, Window::default()).unwrap();
+    let g10 = &stats.guards[&Guard(10)];
+    assert_eq!(g10.days, 2);
+    assert_eq!(g10.total(), 50);
+    assert_eq!(g10.sleepiest_minutes(), vec![24]);
+    assert_eq!(g10.probability(24), 1.0);
+    assert_eq!(g10.probability(30), 0.5);
+
+    let g99 = &stats.guards[&Guard(99)];
+    assert_eq!(g99.sleepiest_minutes(), vec![45]);
+    assert_eq!(g99.sleepiest_minute(Tie::Last), Some(45));
+
+    assert_eq!(stats.pick(|g| g.histogram[40]).unwrap().guard, Guard(99));
+    assert_eq!(answer(&stats, Tie::Last, |g| g.days), 99 * 45);
+
+    // Ties report every minute, and the strategy decides which one to use
+    let tied = SleepStats {
+        guards: vec![(
+            Guard(1),
+            GuardStats {
+                guard: Guard(1),
+                days: 1,
+                histogram: {
+                    let mut h = vec![0; 60];
+                    h[3] = 1;
+                    h[7] = 1;
+                    h
+                },
+            },
+        )]
+        .into_iter()
+        .collect(),
+    };
+    let g1 = &tied.guards[&Guard(1)];
+    assert_eq!(g1.sleepiest_minutes(), vec![3, 7]);
+    assert_eq!(g1.sleepiest_minute(Tie::First), Some(3));
+    assert_eq!(g1.sleepiest_minute(Tie::Last), Some(7));
+    assert_eq!(answer(&tied, Tie::default(), GuardStats::total), 7);
+}
+
+fn part1(data: &[String]) -> Result<usize, ParseError> {
+    Ok(answer(
+        &parse_log(data, Window::default())?,
+        Tie::default(),
+        GuardStats::total,
+    ))
+}
+
+fn part2(data: &[String]) -> Result<usize, ParseError> {
+    Ok(answer(
+        &parse_log(data, Window::default())?,
+        Tie::default(),
+        GuardStats::max_count,
+    ))
 }
 
 #[test]
@@ -216,5 +953,54 @@ fn main() -> io::Result<()> {
     let data = util::read_lines("input.txt")?;
     println!("Part 1: {:?}", part1(&data));
     println!("Part 2: {:?}", part2(&data));
+
+    // Optional chart output: `day04 chart [text|html|svg] [HH:MM-HH:MM]`, and
+    // per-guard statistics: `day04 stats [first|last] [HH:MM-HH:MM]`, where
+    // `first`/`last` picks which of several equally sleepy minutes to report,
+    // `last` by default
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    let window = match args.get(2) {
+        Some(w) => w
+            .parse::<Window>()
+            .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?,
+        None => Window::default(),
+    };
+    match args.first().map(String::as_str) {
+        Some("chart") => {
+            let format = match args.get(1).map(String::as_str) {
+                None | Some("text") => ChartFormat::Text,
+                Some("html") => ChartFormat::Html,
+                Some("svg") => ChartFormat::Svg,
+                _ => return Err(io::Error::from(io::ErrorKind::InvalidInput)),
+            };
+            let shifts = parse_events(&data)
+                .ok()
+                .and_then(|events| parse_shifts(&events, window).ok())
+                .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidData))?;
+            print!("{}", chart(&shifts, window, format));
+        }
+        Some("stats") => {
+            let tie = match args.get(1).map(String::as_str) {
+                Some("first") => Tie::First,
+                None | Some("last") => Tie::Last,
+                _ => return Err(io::Error::from(io::ErrorKind::InvalidInput)),
+            };
+            let stats = parse_log(&data, window)
+                .map_err(|_| io::Error::from(io::ErrorKind::InvalidData))?;
+            println!("ID     Days  Asleep  Sleepiest  P(asleep)");
+            for g in stats.iter() {
+                let minute = g.sleepiest_minute(tie);
+                println!(
+                    "#{:<5} {:<5} {:<7} {:<10} {:.2}",
+                    g.guard.0,
+                    g.days,
+                    g.total(),
+                    minute.map_or(String::from("-"), |m| m.to_string()),
+                    minute.map_or(0.0, |m| g.probability(m))
+                );
+            }
+        }
+        _ => (),
+    }
     Ok(())
 }
@@ -1,51 +1,381 @@
 extern crate util;
+use std::collections::HashSet;
+use std::fs::File;
 use std::io;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::str::FromStr;
+use std::thread;
 
-fn part1(data: String) -> Result<usize, ()> {
-    let mut v = Vec::new();
-    for c in data.chars() {
-        match v.last() {
-            None => v.push(c),
-            Some(l) => {
-                if c != *l && c.eq_ignore_ascii_case(l) {
-                    v.pop();
-                } else {
-                    v.push(c);
-                }
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Polymer {
+    units: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PolymerError {
+    InvalidUnit { position: usize, byte: u8 },
+    Io(io::ErrorKind),
+}
+
+impl From<io::Error> for PolymerError {
+    fn from(e: io::Error) -> PolymerError {
+        PolymerError::Io(e.kind())
+    }
+}
+
+/// Decides which pairs of adjacent units annihilate each other. The stack
+/// reducer reacts greedily from left to right, so the result only matches
+/// every other reaction order if the rules are confluent, which holds when
+/// each unit has at most one partner.
+trait ReactionRules {
+    fn reacts(&self, left: u8, right: u8) -> bool;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Polarity;
+
+impl ReactionRules for Polarity {
+    fn reacts(&self, left: u8, right: u8) -> bool {
+        left ^ right == 0x20 && left.is_ascii_alphabetic()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PairTable {
+    pairs: HashSet<(u8, u8)>,
+}
+
+impl PairTable {
+    fn ordered(pairs: &[(u8, u8)]) -> PairTable {
+        PairTable {
+            pairs: pairs.iter().cloned().collect(),
+        }
+    }
+
+    fn complements(pairs: &[(u8, u8)]) -> PairTable {
+        PairTable {
+            pairs: pairs
+                .iter()
+                .flat_map(|&(a, b)| vec![(a, b), (b, a)])
+                .collect(),
+        }
+    }
+
+    fn brackets() -> PairTable {
+        PairTable::ordered(&[(b'(', b')'), (b'[', b']'), (b'{', b'}'), (b'<', b'>')])
+    }
+
+    fn dna() -> PairTable {
+        PairTable::complements(&[(b'A', b'T'), (b'C', b'G')])
+    }
+}
+
+impl ReactionRules for PairTable {
+    fn reacts(&self, left: u8, right: u8) -> bool {
+        self.pairs.contains(&(left, right))
+    }
+}
+
+impl<F: Fn(u8, u8) -> bool> ReactionRules for F {
+    fn reacts(&self, left: u8, right: u8) -> bool {
+        self(left, right)
+    }
+}
+
+fn push<R: ReactionRules + ?Sized>(rules: &R, stack: &mut Vec<u8>, unit: u8) {
+    match stack.last() {
+        Some(&top) if rules.reacts(top, unit) => {
+            stack.pop();
+        }
+        _ => stack.push(unit),
+    }
+}
+
+fn reduce<R: ReactionRules + ?Sized>(rules: &R, units: &[u8]) -> Vec<u8> {
+    let mut stack = Vec::with_capacity(units.len());
+    for &unit in units {
+        push(rules, &mut stack, unit);
+    }
+    stack
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Trace {
+    reactions: Vec<(usize, usize)>,
+    survivors: Vec<(usize, u8)>,
+}
+
+fn trace<R: ReactionRules + ?Sized>(rules: &R, units: &[u8]) -> Trace {
+    let mut stack: Vec<(usize, u8)> = Vec::with_capacity(units.len());
+    let mut reactions = Vec::new();
+    for (i, &unit) in units.iter().enumerate() {
+        match stack.last() {
+            Some(&(j, top)) if rules.reacts(top, unit) => {
+                stack.pop();
+                reactions.push((j, i));
             }
+            _ => stack.push((i, unit)),
         }
     }
-    Ok(v.len())
+    Trace {
+        reactions,
+        survivors: stack,
+    }
 }
 
-fn part2(data: &str) -> Result<usize, ()> {
-    let mut best = data.len();
-    for i in ('a' as u32)..=('z' as u32) {
-        let c = std::char::from_u32(i).ok_or(())?;
-        let s = data
-            .chars()
-            .filter(|a| !a.eq_ignore_ascii_case(&c))
-            .collect::<String>();
-        best = best.min(part1(s)?);
+impl Trace {
+    /// One line per reaction. Only readable for small inputs.
+    fn steps(&self, units: &[u8]) -> String {
+        let mut alive = vec![true; units.len()];
+        let show = |alive: &[bool]| {
+            units
+                .iter()
+                .zip(alive)
+                .filter(|(_, &a)| a)
+                .map(|(&u, _)| u as char)
+                .collect::<String>()
+        };
+        let width = units.len() + 2;
+        let mut out = show(&alive) + "\n";
+        for &(l, r) in &self.reactions {
+            alive[l] = false;
+            alive[r] = false;
+            out += &format!(
+                "{:w$}{}{} at {}, {}\n",
+                show(&alive),
+                units[l] as char,
+                units[r] as char,
+                l,
+                r,
+                w = width
+            );
+        }
+        out
     }
-    Ok(best)
+}
+
+#[test]
+fn trace_test() {
+    let units = b"dabAcCaCBAcCcaDA";
+    let t = trace(&Polarity, units);
+    assert_eq!(t.reactions, vec![(4, 5), (3, 6), (10, 11)]);
+    assert_eq!(
+        t.survivors.iter().map(|&(i, _)| i).collect::<Vec<usize>>(),
+        vec![0, 1, 2, 7, 8, 9, 12, 13, 14, 15]
+    );
+    assert_eq!(
+        t.steps(units),
+        "\
+dabAcCaCBAcCcaDA
+dabAaCBAcCcaDA    cC at 4, 5
+dabCBAcCcaDA      Aa at 3, 6
+dabCBAcaDA        cC at 10, 11
+"
+    );
+}
+
+#[test]
+fn rules_test() {
+    assert_eq!(reduce(&PairTable::brackets(), b"([]{<>})"), b"");
+    assert_eq!(reduce(&PairTable::brackets(), b"(]())"), b"(])");
+    assert_eq!(reduce(&PairTable::dna(), b"GATTACA"), b"A");
+    // Only adjacent digits summing to ten react
+    assert_eq!(
+        reduce(&|a: u8, b: u8| a + b == b'0' * 2 + 10, b"1937465"),
+        b"5"
+    );
+}
+
+fn scan<R, F>(reader: R, mut f: F) -> Result<(), PolymerError>
+where
+    R: Read,
+    F: FnMut(u8),
+{
+    // First whitespace byte of the current run and its offset, which is only
+    // an error if a unit follows it
+    let mut space = None;
+    for (position, byte) in BufReader::new(reader).bytes().enumerate() {
+        let byte = byte?;
+        if byte.is_ascii_alphabetic() {
+            if let Some((position, byte)) = space {
+                return Err(PolymerError::InvalidUnit { position, byte });
+            }
+            f(byte);
+        } else if byte.is_ascii_whitespace() {
+            space = space.or(Some((position, byte)));
+        } else {
+            return Err(PolymerError::InvalidUnit { position, byte });
+        }
+    }
+    Ok(())
+}
+
+impl Polymer {
+    fn from_reader<R: Read>(reader: R) -> Result<Polymer, PolymerError> {
+        let mut units = Vec::new();
+        scan(reader, |unit| units.push(unit))?;
+        Ok(Polymer { units })
+    }
+
+    /// React while reading, keeping only the stack of surviving units
+    fn react_reader<R: Read>(reader: R) -> Result<Polymer, PolymerError> {
+        let mut units = Vec::new();
+        scan(reader, |unit| push(&Polarity, &mut units, unit))?;
+        Ok(Polymer { units })
+    }
+
+    fn react(&self) -> Polymer {
+        Polymer {
+            units: reduce(&Polarity, &self.units),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.units.len()
+    }
+}
+
+impl FromStr for Polymer {
+    type Err = PolymerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Polymer::from_reader(s.as_bytes())
+    }
+}
+
+#[test]
+fn polymer_parse_test() {
+    assert_eq!(
+        "dabAcCaCBAcCcaDA\r\n".parse::<Polymer>().map(|p| p.len()),
+        Ok(16)
+    );
+    assert_eq!(
+        "dabA1".parse::<Polymer>(),
+        Err(PolymerError::InvalidUnit {
+            position: 4,
+            byte: b'1'
+        })
+    );
+    assert_eq!(
+        "dab Acc\n".parse::<Polymer>(),
+        Err(PolymerError::InvalidUnit {
+            position: 3,
+            byte: b' '
+        })
+    );
+}
+
+fn part1(polymer: &Polymer) -> usize {
+    polymer.react().len()
+}
+
+fn shortest_without<R: ReactionRules>(rules: &R, reduced: &[u8], letters: &[u8]) -> usize {
+    let mut stacks = letters
+        .iter()
+        .map(|_| Vec::with_capacity(reduced.len()))
+        .collect::<Vec<Vec<u8>>>();
+    for &unit in reduced {
+        let lower = unit.to_ascii_lowercase();
+        for (stack, &letter) in stacks.iter_mut().zip(letters) {
+            if lower != letter {
+                push(rules, stack, unit);
+            }
+        }
+    }
+    stacks.iter().map(Vec::len).min().unwrap_or(reduced.len())
+}
+
+/// Removing a unit type commutes with reacting, so all 26 removals can start
+/// from the once-reduced polymer
+fn part2(polymer: &Polymer, threads: usize) -> usize {
+    let reduced = &polymer.react().units[..];
+    let letters = (b'a'..=b'z').collect::<Vec<u8>>();
+    if threads <= 1 {
+        return shortest_without(&Polarity, reduced, &letters);
+    }
+    let chunk = letters.len().div_ceil(threads);
+    thread::scope(|s| {
+        letters
+            .chunks(chunk)
+            .map(|letters| s.spawn(move || shortest_without(&Polarity, reduced, letters)))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .min()
+            .unwrap_or(reduced.len())
+    })
 }
 
 #[test]
 fn part1_test() {
-    let data = util::read("test1.txt").unwrap();
-    assert_eq!(part1(data), Ok(10));
+    let polymer = util::read("test1.txt").unwrap().parse().unwrap();
+    assert_eq!(part1(&polymer), 10);
+    assert_eq!(
+        Polymer::react_reader(File::open("test1.txt").unwrap()).map(|p| p.units),
+        Ok(b"dabCBAcaDA".to_vec())
+    );
 }
 
 #[test]
 fn part2_test() {
-    let data = util::read("test1.txt").unwrap();
-    assert_eq!(part2(&data), Ok(4));
+    let polymer = util::read("test1.txt").unwrap().parse().unwrap();
+    assert_eq!(part2(&polymer, 1), 4);
+    assert_eq!(part2(&polymer, 4), 4);
+}
+
+#[test]
+fn reacts_test() {
+    assert!(Polarity.reacts(b'a', b'A'));
+    assert!(!Polarity.reacts(b'a', b'a'));
+    assert!(!Polarity.reacts(b'a', b'B'));
+    // '@' and '`' also differ only in bit 5
+    assert!(!Polarity.reacts(b'@', b'`'));
 }
 
 fn main() -> io::Result<()> {
-    let data = util::read("input.txt")?;
-    println!("Part 1: {:?}", part1(data.clone()));
-    println!("Part 2: {:?}", part2(&data));
+    // Reacting while reading is safe, since both parts start by reacting
+    let polymer = Polymer::react_reader(File::open("input.txt")?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", e)))?;
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    println!("Part 1: {:?}", part1(&polymer));
+    println!("Part 2: {:?}", part2(&polymer, threads));
+
+    // Reduce another file under different rules:
+    // `day05 react <brackets|dna> <path>` prints the surviving units
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    if args.first().map(String::as_str) == Some("react") {
+        let rules = match args.get(1).map(String::as_str) {
+            Some("brackets") => PairTable::brackets(),
+            Some("dna") => PairTable::dna(),
+            _ => return Err(io::Error::from(io::ErrorKind::InvalidInput)),
+        };
+        let path = args
+            .get(2)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?;
+        let data = util::read(path)?;
+        let survivors = reduce(&rules, data.trim_end().as_bytes());
+        println!("{}", String::from_utf8_lossy(&survivors));
+    }
+
+    // Show what reacted with what: `day05 trace <path>`. Small polymers get
+    // every reduction step, larger ones just the surviving units.
+    if args.first().map(String::as_str) == Some("trace") {
+        let path = args
+            .get(1)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?;
+        let polymer = util::read(path)?
+            .parse::<Polymer>()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", e)))?;
+        let t = trace(&Polarity, &polymer.units);
+        if polymer.len() <= 80 {
+            print!("{}", t.steps(&polymer.units));
+        } else {
+            println!("{} reactions", t.reactions.len());
+        }
+        for (i, unit) in t.survivors {
+            println!("{:>8} {}", i, unit as char);
+        }
+    }
     Ok(())
 }
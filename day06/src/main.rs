@@ -1,4 +1,5 @@
 extern crate util;
+use std::collections::VecDeque;
 use std::io;
 use std::num::ParseIntError;
 use std::str::FromStr;
@@ -24,113 +25,714 @@ impl FromStr for Coord {
             .collect::<Result<Vec<i32>, ParseIntError>>()
             .map_err(|_| CoordError::ParseIntError)?;
         Ok(Coord {
-            x: *n.get(0).ok_or(CoordError::InvalidData)?,
+            x: *n.first().ok_or(CoordError::InvalidData)?,
             y: *n.get(1).ok_or(CoordError::InvalidData)?,
         })
     }
 }
 
-impl Coord {
-    fn distance(self, other: Coord) -> i32 {
-        (self.x - other.x).abs() + (self.y - other.y).abs()
+/// A cell equally close to two or more coordinates belongs to none of them
+#[derive(Debug, Copy, Clone)]
+enum Metric {
+    Manhattan,
+    Chebyshev,
+    /// `dx² + dy²`, which picks the same nearest coordinates as Euclidean
+    /// distance, but makes the part 2 cutoff a limit on squared distances
+    SquaredEuclidean,
+    Custom(fn(Coord, Coord) -> i64),
+}
+
+impl Metric {
+    fn distance(self, a: Coord, b: Coord) -> i64 {
+        let dx = (a.x as i64 - b.x as i64).abs();
+        let dy = (a.y as i64 - b.y as i64).abs();
+        match self {
+            Metric::Manhattan => dx + dy,
+            Metric::Chebyshev => dx.max(dy),
+            Metric::SquaredEuclidean => dx * dx + dy * dy,
+            Metric::Custom(f) => f(a, b),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Owner {
+    Unvisited,
+    Coord(usize),
+    Tie,
+}
+
+impl Owner {
+    fn merge(self, other: Owner) -> Owner {
+        match (self, other) {
+            (Owner::Unvisited, o) | (o, Owner::Unvisited) => o,
+            (Owner::Coord(a), Owner::Coord(b)) if a == b => self,
+            _ => Owner::Tie,
+        }
     }
 }
 
-fn part1(data: &[String]) -> Result<usize, CoordError> {
+struct Grid {
+    min: Coord,
+    width: usize,
+    height: usize,
+    cells: Vec<Owner>,
+}
+
+impl Grid {
+    fn new(min: Coord, max: Coord) -> Grid {
+        let width = (max.x - min.x + 1) as usize;
+        let height = (max.y - min.y + 1) as usize;
+        Grid {
+            min,
+            width,
+            height,
+            cells: vec![Owner::Unvisited; width * height],
+        }
+    }
+
+    fn index(&self, c: Coord) -> Option<usize> {
+        let x = c.x.checked_sub(self.min.x)?;
+        let y = c.y.checked_sub(self.min.y)?;
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return None;
+        }
+        Some(y as usize * self.width + x as usize)
+    }
+
+    fn coord(&self, idx: usize) -> Coord {
+        Coord {
+            x: self.min.x + (idx % self.width) as i32,
+            y: self.min.y + (idx / self.width) as i32,
+        }
+    }
+
+    fn on_edge(&self, idx: usize) -> bool {
+        let (x, y) = (idx % self.width, idx / self.width);
+        x == 0 || y == 0 || x + 1 == self.width || y + 1 == self.height
+    }
+
+    /// Multi-source breadth first search from every coordinate at once. A
+    /// cell's nearest coordinates are the union of those of its neighbours
+    /// one step closer, so ties propagate outward in O(W·H).
+    fn flood(&mut self, coords: &[Coord], steps: &[(i32, i32)]) {
+        let mut dist = vec![u32::MAX; self.cells.len()];
+        let mut queue = VecDeque::new();
+        for (i, &c) in coords.iter().enumerate() {
+            let idx = self.index(c).expect("coordinate inside grid");
+            if dist[idx] == u32::MAX {
+                dist[idx] = 0;
+                queue.push_back(idx);
+            }
+            self.cells[idx] = self.cells[idx].merge(Owner::Coord(i));
+        }
+        while let Some(idx) = queue.pop_front() {
+            let c = self.coord(idx);
+            for &(dx, dy) in steps {
+                let next = match self.index(Coord {
+                    x: c.x + dx,
+                    y: c.y + dy,
+                }) {
+                    Some(next) => next,
+                    None => continue,
+                };
+                if dist[next] == u32::MAX {
+                    dist[next] = dist[idx] + 1;
+                    queue.push_back(next);
+                }
+                if dist[next] == dist[idx] + 1 {
+                    self.cells[next] = self.cells[next].merge(self.cells[idx]);
+                }
+            }
+        }
+    }
+
+    fn scan(&mut self, coords: &[Coord], metric: Metric) {
+        for idx in 0..self.cells.len() {
+            let c = self.coord(idx);
+            let mut best = i64::MAX;
+            let mut owner = Owner::Unvisited;
+            for (i, &p) in coords.iter().enumerate() {
+                let d = metric.distance(p, c);
+                if d < best {
+                    best = d;
+                    owner = Owner::Coord(i);
+                } else if d == best {
+                    owner = Owner::Tie;
+                }
+            }
+            self.cells[idx] = owner;
+        }
+    }
+}
+
+fn bounds(coords: &[Coord]) -> Option<(Coord, Coord)> {
+    let first = *coords.first()?;
+    Some(coords.iter().fold((first, first), |(min, max), &c| {
+        (
+            Coord {
+                x: min.x.min(c.x),
+                y: min.y.min(c.y),
+            },
+            Coord {
+                x: max.x.max(c.x),
+                y: max.y.max(c.y),
+            },
+        )
+    }))
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Region {
+    area: usize,
+    infinite: bool,
+}
+
+/// Manhattan/Chebyshev: a region is infinite iff it owns an edge cell of the
+/// (grown) box; other metrics: heuristic
+fn ownership(coords: &[Coord], metric: Metric, extra: i32) -> Option<Grid> {
+    let (min, max) = bounds(coords)?;
+    let margin = match metric {
+        Metric::Manhattan => 0,
+        _ => (max.x - min.x).max(max.y - min.y) + 1,
+    }
+    .max(extra);
+    let mut grid = Grid::new(
+        Coord {
+            x: min.x - margin,
+            y: min.y - margin,
+        },
+        Coord {
+            x: max.x + margin,
+            y: max.y + margin,
+        },
+    );
+    match metric {
+        Metric::Manhattan => grid.flood(coords, &[(1, 0), (-1, 0), (0, 1), (0, -1)]),
+        Metric::Chebyshev => grid.flood(
+            coords,
+            &[
+                (1, 0),
+                (-1, 0),
+                (0, 1),
+                (0, -1),
+                (1, 1),
+                (1, -1),
+                (-1, 1),
+                (-1, -1),
+            ],
+        ),
+        _ => grid.scan(coords, metric),
+    }
+    Some(grid)
+}
+
+fn regions(coords: &[Coord], grid: &Grid) -> Vec<Region> {
+    let mut regions = vec![
+        Region {
+            area: 0,
+            infinite: false
+        };
+        coords.len()
+    ];
+    for (idx, &owner) in grid.cells.iter().enumerate() {
+        if let Owner::Coord(i) = owner {
+            regions[i].area += 1;
+            regions[i].infinite |= grid.on_edge(idx);
+        }
+    }
+    regions
+}
+
+fn part1(data: &[String], metric: Metric) -> Result<usize, CoordError> {
     let coords = data
         .iter()
         .map(|s| s.parse::<Coord>())
         .collect::<Result<Vec<Coord>, CoordError>>()?;
-
-    // Define the size of our search space
-    let max = coords.iter().fold(Coord { x: 0, y: 0 }, |acc, &c| Coord {
-        x: acc.x.max(c.x),
-        y: acc.y.max(c.y),
-    });
-    let min = coords
+    let grid = ownership(&coords, metric, 0).ok_or(CoordError::InvalidData)?;
+    regions(&coords, &grid)
         .iter()
-        .fold(Coord { x: max.x, y: max.y }, |acc, &c| Coord {
-            x: acc.x.min(c.x),
-            y: acc.y.min(c.y),
-        });
-    let mut infinite = (0..coords.len()).map(|_| true).collect::<Vec<bool>>();
-    let mut scores = (0..coords.len()).map(|_| 0).collect::<Vec<i32>>();
-    for x in min.x..=max.x {
-        for y in min.y..=max.y {
-            let c = Coord { x, y };
-            let s = coords.iter().map(|x| x.distance(c)).collect::<Vec<i32>>();
-            let m = s.iter().min().ok_or(CoordError::InvalidData)?;
-            let idx = s
-                .iter()
-                .enumerate()
-                .filter_map(|(i, x)| if x == m { Some(i) } else { None })
-                .collect::<Vec<usize>>();
-
-            if idx.len() == 1 {
-                scores[idx[0]] += 1;
-                if x == min.x || x == max.x || y == min.y || y == max.y {
-                    infinite[idx[0]] = false;
-                }
-            } else if idx.is_empty() {
-                return Err(CoordError::InvalidData);
-            }
+        .filter(|r| !r.infinite)
+        .map(|r| r.area)
+        .max()
+        .ok_or(CoordError::InvalidData)
+}
+
+/// Sum of `|v - vi|` over one axis, from sorted values and prefix sums
+struct AxisSums {
+    sorted: Vec<i64>,
+    prefix: Vec<i64>,
+}
+
+impl AxisSums {
+    fn new(mut values: Vec<i64>) -> AxisSums {
+        values.sort_unstable();
+        let mut prefix = vec![0];
+        for &v in &values {
+            prefix.push(prefix[prefix.len() - 1] + v);
+        }
+        AxisSums {
+            sorted: values,
+            prefix,
         }
     }
-    Ok(*scores
+
+    fn at(&self, v: i64) -> i64 {
+        let n = self.sorted.len();
+        let k = self.sorted.partition_point(|&x| x <= v);
+        let below = v * k as i64 - self.prefix[k];
+        let above = (self.prefix[n] - self.prefix[k]) - v * (n - k) as i64;
+        below + above
+    }
+
+    /// Sums at every position where the sum is below `cutoff`. The sum is
+    /// convex, so these positions form one contiguous run around the median.
+    fn below(&self, cutoff: i64) -> Vec<i64> {
+        let median = match self.sorted.get(self.sorted.len() / 2) {
+            Some(&m) => m,
+            None => return Vec::new(),
+        };
+        let mut sums = Vec::new();
+        let mut v = median;
+        while self.at(v) < cutoff {
+            sums.push(self.at(v));
+            v -= 1;
+        }
+        let mut v = median + 1;
+        while self.at(v) < cutoff {
+            sums.push(self.at(v));
+            v += 1;
+        }
+        sums
+    }
+}
+
+/// The total separates into `Sx(x) + Sy(y)`, so each valid column is
+/// matched against the sorted valid row sums, counting cells outside the
+/// bounding box too, in O((W + H) log n)
+fn safe_area_manhattan(coords: &[Coord], cutoff: i64) -> usize {
+    let xs = AxisSums::new(coords.iter().map(|c| c.x as i64).collect());
+    let ys = AxisSums::new(coords.iter().map(|c| c.y as i64).collect());
+    let mut rows = ys.below(cutoff);
+    rows.sort_unstable();
+    xs.below(cutoff)
         .iter()
-        .zip(infinite.iter())
-        .filter(|(_, &c)| c)
-        .map(|(s, _)| s)
-        .max()
-        .ok_or(CoordError::InvalidData)? as usize)
+        .map(|&sx| rows.partition_point(|&sy| sx + sy < cutoff))
+        .sum()
+}
+
+/// How far the safe region can reach outside the bounding box. A cell `m`
+/// steps outside is at least `m` away from every coordinate in any metric at
+/// least as large as Chebyshev, so its total is at least `n·m`.
+fn safe_margin(cutoff: i64, n: usize) -> i32 {
+    ((cutoff - 1) / n.max(1) as i64).max(0) as i32
 }
 
-fn part2(data: &[String], cutoff: i32) -> Result<usize, CoordError> {
+fn part2(data: &[String], cutoff: i64, metric: Metric) -> Result<usize, CoordError> {
     let coords = data
         .iter()
         .map(|s| s.parse::<Coord>())
         .collect::<Result<Vec<Coord>, CoordError>>()?;
+    if coords.is_empty() {
+        return Err(CoordError::InvalidData);
+    }
+    if let Metric::Manhattan = metric {
+        return Ok(safe_area_manhattan(&coords, cutoff));
+    }
 
-    // Define the size of our search space
-    let max = coords.iter().fold(Coord { x: 0, y: 0 }, |acc, &c| Coord {
-        x: acc.x.max(c.x),
-        y: acc.y.max(c.y),
-    });
-    let min = coords
-        .iter()
-        .fold(Coord { x: max.x, y: max.y }, |acc, &c| Coord {
-            x: acc.x.min(c.x),
-            y: acc.y.min(c.y),
-        });
-
-    let mut safe_region = Vec::new();
-    for x in min.x..=max.x {
-        for y in min.y..=max.y {
+    let (min, max) = bounds(&coords).ok_or(CoordError::InvalidData)?;
+    let margin = safe_margin(cutoff, coords.len());
+
+    let mut safe_region = 0;
+    for x in min.x - margin..=max.x + margin {
+        for y in min.y - margin..=max.y + margin {
             let c = Coord { x, y };
-            let s = coords.iter().map(|x| x.distance(c)).collect::<Vec<i32>>();
-            if s.iter().sum::<i32>() < cutoff {
-                safe_region.push(c);
+            let s = coords
+                .iter()
+                .map(|&x| metric.distance(x, c))
+                .collect::<Vec<i64>>();
+            if s.iter().sum::<i64>() < cutoff {
+                safe_region += 1;
             }
         }
     }
-    Ok(safe_region.len())
+    Ok(safe_region)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Format {
+    Ascii,
+    Ppm,
+    Svg,
+}
+
+fn letter(i: usize) -> u8 {
+    b'a' + (i % 26) as u8
+}
+
+fn color(owner: Owner, regions: &[Region]) -> [u8; 3] {
+    let i = match owner {
+        Owner::Coord(i) => i,
+        _ => return [128, 128, 128],
+    };
+    let h = (i as f64 * 0.618_034).fract() * 6.0;
+    let v = if regions[i].infinite { 110.0 } else { 230.0 };
+    let f = h.fract();
+    let (p, q, t) = (v * 0.35, v * (1.0 - 0.65 * f), v * (1.0 - 0.65 * (1.0 - f)));
+    let (r, g, b) = match h as u32 {
+        0 => (v, t, p),
+        1 => (q, v, p),
+        2 => (p, v, t),
+        3 => (p, q, v),
+        4 => (t, p, v),
+        _ => (v, p, q),
+    };
+    [r as u8, g as u8, b as u8]
+}
+
+/// With a `cutoff`, the map is grown to hold the whole safe region
+fn render(
+    coords: &[Coord],
+    metric: Metric,
+    cutoff: Option<i64>,
+    format: Format,
+) -> Option<Vec<u8>> {
+    let extra = cutoff.map_or(0, |c| safe_margin(c, coords.len()) + 1);
+    let grid = ownership(coords, metric, extra)?;
+    let (width, height) = (grid.width, grid.height);
+    let regions = regions(coords, &grid);
+    let mut is_coord = vec![false; grid.cells.len()];
+    for &c in coords {
+        is_coord[grid.index(c)?] = true;
+    }
+    let safe = match (cutoff, metric) {
+        (None, _) => vec![false; grid.cells.len()],
+        (Some(cutoff), Metric::Manhattan) => {
+            let xs = AxisSums::new(coords.iter().map(|c| c.x as i64).collect());
+            let ys = AxisSums::new(coords.iter().map(|c| c.y as i64).collect());
+            let sx = (0..width)
+                .map(|x| xs.at((grid.min.x + x as i32) as i64))
+                .collect::<Vec<i64>>();
+            let sy = (0..height)
+                .map(|y| ys.at((grid.min.y + y as i32) as i64))
+                .collect::<Vec<i64>>();
+            (0..grid.cells.len())
+                .map(|idx| sx[idx % width] + sy[idx / width] < cutoff)
+                .collect()
+        }
+        (Some(cutoff), _) => (0..grid.cells.len())
+            .map(|idx| {
+                let c = grid.coord(idx);
+                coords.iter().map(|&p| metric.distance(p, c)).sum::<i64>() < cutoff
+            })
+            .collect(),
+    };
+    let is_safe = |x: i64, y: i64| {
+        x >= 0 && y >= 0 && x < width as i64 && y < height as i64 && {
+            safe[y as usize * width + x as usize]
+        }
+    };
+    let outline = |x: i64, y: i64| {
+        is_safe(x, y)
+            && !(is_safe(x - 1, y) && is_safe(x + 1, y) && is_safe(x, y - 1) && is_safe(x, y + 1))
+    };
+
+    Some(match format {
+        Format::Ascii => {
+            let mut out = Vec::with_capacity((width + 1) * height);
+            for y in 0..height {
+                for x in 0..width {
+                    let idx = y * width + x;
+                    let edge = outline(x as i64, y as i64);
+                    out.push(match grid.cells[idx] {
+                        Owner::Coord(i) if is_coord[idx] => letter(i).to_ascii_uppercase(),
+                        _ if edge => b'#',
+                        Owner::Coord(i) => letter(i),
+                        _ => b'.',
+                    });
+                }
+                out.push(b'\n');
+            }
+            out
+        }
+        Format::Ppm => {
+            let mut pixels = Vec::with_capacity(width * height * 3);
+            for y in 0..height {
+                for x in 0..width {
+                    let idx = y * width + x;
+                    if is_coord[idx] {
+                        pixels.extend_from_slice(&[0, 0, 0]);
+                    } else if outline(x as i64, y as i64) {
+                        pixels.extend_from_slice(&[255, 255, 255]);
+                    } else {
+                        pixels.extend_from_slice(&color(grid.cells[idx], &regions));
+                    }
+                }
+            }
+            util::ppm(width, height, &pixels)
+        }
+        Format::Svg => {
+            let mut out = format!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\" \
+                 shape-rendering=\"crispEdges\">\n",
+                grid.min.x, grid.min.y, width, height
+            );
+            // Runs of equally owned cells in a row share one rectangle
+            for y in 0..height {
+                let mut x = 0;
+                while x < width {
+                    let owner = grid.cells[y * width + x];
+                    let start = x;
+                    while x < width && grid.cells[y * width + x] == owner {
+                        x += 1;
+                    }
+                    let [r, g, b] = color(owner, &regions);
+                    out += &format!(
+                        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"1\" \
+                         fill=\"#{:02x}{:02x}{:02x}\"/>\n",
+                        grid.min.x + start as i32,
+                        grid.min.y + y as i32,
+                        x - start,
+                        r,
+                        g,
+                        b
+                    );
+                }
+            }
+            // Outline the safe region along every cell side it shares with
+            // an unsafe cell
+            let mut path = String::new();
+            for y in 0..height as i64 {
+                for x in 0..width as i64 {
+                    if !is_safe(x, y) {
+                        continue;
+                    }
+                    let (px, py) = (grid.min.x as i64 + x, grid.min.y as i64 + y);
+                    if !is_safe(x, y - 1) {
+                        path += &format!("M{} {}h1", px, py);
+                    }
+                    if !is_safe(x, y + 1) {
+                        path += &format!("M{} {}h1", px, py + 1);
+                    }
+                    if !is_safe(x - 1, y) {
+                        path += &format!("M{} {}v1", px, py);
+                    }
+                    if !is_safe(x + 1, y) {
+                        path += &format!("M{} {}v1", px + 1, py);
+                    }
+                }
+            }
+            if !path.is_empty() {
+                out += &format!(
+                    "<path d=\"{}\" fill=\"none\" stroke=\"white\" stroke-width=\"0.3\"/>\n",
+                    path
+                );
+            }
+            for (i, c) in coords.iter().enumerate() {
+                out += &format!(
+                    "<circle cx=\"{}.5\" cy=\"{}.5\" r=\"0.4\" fill=\"black\"><title>{} ({}, {}){}</title></circle>\n",
+                    c.x,
+                    c.y,
+                    i,
+                    c.x,
+                    c.y,
+                    if regions[i].infinite { " infinite" } else { "" }
+                );
+            }
+            out += "</svg>\n";
+            out.into_bytes()
+        }
+    })
+}
+
+fn report(coords: &[Coord], grid: &Grid) -> String {
+    let mut out = format!(
+        "{:>6}  {:>10}  {:>6}  {}\n",
+        "Region", "Coordinate", "Area", "Finite"
+    );
+    for (i, (c, r)) in coords.iter().zip(regions(coords, grid)).enumerate() {
+        out += &format!(
+            "{:>6}  {:>10}  {:>6}  {}\n",
+            i,
+            format!("{}, {}", c.x, c.y),
+            r.area,
+            if r.infinite { "no" } else { "yes" }
+        );
+    }
+    out
+}
+
+#[test]
+fn render_test() {
+    let data = util::read_lines("test1.txt").unwrap();
+    let coords = data
+        .iter()
+        .map(|s| s.parse::<Coord>())
+        .collect::<Result<Vec<Coord>, CoordError>>()
+        .unwrap();
+    // The bounding box of the puzzle diagram, x in 1..=8 and y in 1..=9
+    let ascii = render(&coords, Metric::Manhattan, None, Format::Ascii).unwrap();
+    assert_eq!(
+        String::from_utf8(ascii).unwrap(),
+        "\
+Aaaa.ccc
+aaddeccc
+adddeccC
+.dDdeecc
+b.deEeec
+Bb.eeee.
+bb.eeeff
+bb.eefff
+bb.ffffF
+"
+    );
+    let ppm = render(&coords, Metric::Manhattan, None, Format::Ppm).unwrap();
+    assert!(ppm.starts_with(b"P6\n8 9\n255\n"));
+    assert_eq!(ppm.len(), 11 + 8 * 9 * 3);
+
+    // With a cutoff the map grows by 6 on each side to hold the safe region
+    let ascii = render(&coords, Metric::Manhattan, Some(32), Format::Ascii).unwrap();
+    let ascii = String::from_utf8(ascii).unwrap();
+    assert_eq!(ascii.lines().nth(9).map(|l| &l[6..14]), Some(".#Dde#cc"));
+
+    let origin = [Coord { x: 0, y: 0 }];
+    let ascii = render(&origin, Metric::Manhattan, Some(3), Format::Ascii).unwrap();
+    assert_eq!(
+        String::from_utf8(ascii).unwrap(),
+        "\
+aaaaaaa
+aaa#aaa
+aa#a#aa
+a#aAa#a
+aa#a#aa
+aaa#aaa
+aaaaaaa
+"
+    );
+
+    let grid = ownership(&coords, Metric::Manhattan, 0).unwrap();
+    let table = report(&coords, &grid);
+    assert_eq!(
+        table.lines().nth(5),
+        Some("     4        5, 5      17  yes")
+    );
+    assert_eq!(table.lines().nth(1), Some("     0        1, 1       7  no"));
+}
+
+#[test]
+fn safe_area_test() {
+    let origin = [Coord { x: 0, y: 0 }];
+    assert_eq!(safe_area_manhattan(&origin, 10_000), 199_980_001);
+
+    let data = util::read_lines("test1.txt").unwrap();
+    let manhattan = Metric::Custom(|a, b| Metric::Manhattan.distance(a, b));
+    for &cutoff in &[1, 32, 100, 500] {
+        assert_eq!(
+            part2(&data, cutoff, Metric::Manhattan),
+            part2(&data, cutoff, manhattan)
+        );
+    }
+    assert_eq!(part2(&data, 100, Metric::Manhattan), Ok(516));
 }
 
 #[test]
 fn part1_test() {
     let data = util::read_lines("test1.txt").unwrap();
-    assert_eq!(part1(&data), Ok(17));
+    assert_eq!(part1(&data, Metric::Manhattan), Ok(17));
+    assert_eq!(
+        part1(
+            &data,
+            Metric::Custom(|a, b| Metric::Manhattan.distance(a, b))
+        ),
+        Ok(17)
+    );
+}
+
+#[test]
+fn ownership_test() {
+    // (6, 5) touches the bounding box but is boxed in by its neighbours
+    // under Chebyshev distance, which the edge of the box alone cannot show
+    let data = util::lines(&["3, 4", "6, 4", "6, 8", "6, 5"]);
+    assert_eq!(part1(&data, Metric::Chebyshev), Ok(4));
+}
+
+#[test]
+fn metric_test() {
+    let (a, b) = (Coord { x: 1, y: 1 }, Coord { x: 4, y: 5 });
+    assert_eq!(Metric::Manhattan.distance(a, b), 7);
+    assert_eq!(Metric::Chebyshev.distance(a, b), 4);
+    assert_eq!(Metric::SquaredEuclidean.distance(a, b), 25);
+
+    let data = util::read_lines("test1.txt").unwrap();
+    assert_eq!(part1(&data, Metric::Chebyshev), Ok(10));
+    assert_eq!(part1(&data, Metric::SquaredEuclidean), Ok(16));
+
+    let data = util::lines(&["1, 1", "3, 1", "2, 5"]);
+    assert_eq!(part2(&data, 9, Metric::Chebyshev), Ok(24));
+    assert_eq!(part2(&data, 9, Metric::Manhattan), Ok(7));
 }
 
 #[test]
 fn part2_test() {
     let data = util::read_lines("test1.txt").unwrap();
-    assert_eq!(part2(&data, 32), Ok(16));
+    assert_eq!(part2(&data, 32, Metric::Manhattan), Ok(16));
 }
 
 fn main() -> io::Result<()> {
     let data = util::read_lines("input.txt")?;
-    println!("Part 1: {:?}", part1(&data));
-    println!("Part 2: {:?}", part2(&data, 10_000));
+    // Optional metric, then an optional map or region table:
+    // `day06 [manhattan|chebyshev|euclidean|octile]
+    //        [render <ascii|ppm|svg> [path] | regions]`, where `euclidean`
+    // sums squared distances against the part 2 cutoff
+    let mut args = std::env::args().skip(1).peekable();
+    let metric = match args.peek().map(String::as_str) {
+        None | Some("render") | Some("regions") => Metric::Manhattan,
+        Some("manhattan") => Metric::Manhattan,
+        Some("chebyshev") => Metric::Chebyshev,
+        Some("euclidean") => Metric::SquaredEuclidean,
+        // Diagonal steps cost roughly √2, scaled by 10 to stay in integers
+        Some("octile") => Metric::Custom(|a, b| {
+            let dx = (a.x as i64 - b.x as i64).abs();
+            let dy = (a.y as i64 - b.y as i64).abs();
+            10 * dx.max(dy) + 4 * dx.min(dy)
+        }),
+        _ => return Err(io::Error::from(io::ErrorKind::InvalidInput)),
+    };
+    if let Some("manhattan" | "chebyshev" | "euclidean" | "octile") =
+        args.peek().map(String::as_str)
+    {
+        args.next();
+    }
+
+    let invalid = || io::Error::from(io::ErrorKind::InvalidData);
+    let coords = data
+        .iter()
+        .map(|s| s.parse::<Coord>())
+        .collect::<Result<Vec<Coord>, CoordError>>()
+        .map_err(|_| invalid())?;
+    match args.next().as_deref() {
+        Some("render") => {
+            let format = match args.next().as_deref() {
+                Some("ascii") => Format::Ascii,
+                Some("ppm") => Format::Ppm,
+                Some("svg") => Format::Svg,
+                _ => return Err(io::Error::from(io::ErrorKind::InvalidInput)),
+            };
+            let image = render(&coords, metric, Some(10_000), format).ok_or_else(invalid)?;
+            match args.next() {
+                Some(path) => std::fs::write(path, image)?,
+                None => io::Write::write_all(&mut io::stdout(), &image)?,
+            }
+        }
+        Some("regions") => {
+            let grid = ownership(&coords, metric, 0).ok_or_else(invalid)?;
+            print!("{}", report(&coords, &grid));
+        }
+        None => {
+            println!("Part 1: {:?}", part1(&data, metric));
+            println!("Part 2: {:?}", part2(&data, 10_000, metric));
+        }
+        _ => return Err(io::Error::from(io::ErrorKind::InvalidInput)),
+    }
     Ok(())
 }
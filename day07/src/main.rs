@@ -1,217 +1,960 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::convert::TryFrom;
+use std::hash::Hash;
 use std::io;
-use util;
+use std::str::FromStr;
 
-///Directed acyclic graph
-#[derive(Default, Debug, Clone, PartialEq)]
-struct Node {
-    id: char,
-    incoming: Vec<char>,
-    outgoing: Vec<char>,
+/// Directed acyclic graph over any node label
+#[derive(Debug, Clone)]
+struct Dag<K> {
+    labels: Vec<K>,
+    index: HashMap<K, usize>,
+    outgoing: Vec<Vec<usize>>,
+    in_degree: Vec<usize>,
 }
 
-#[derive(Default, Debug, Copy, Clone)]
-struct Job {
-    letter: Option<char>,
-    clock: u32,
-}
-
-impl Node {
-    fn new(id: char) -> Node {
-        Node {
-            id,
-            incoming: Vec::new(),
+impl<K: Clone + Eq + Hash> Dag<K> {
+    fn new() -> Dag<K> {
+        Dag {
+            labels: Vec::new(),
+            index: HashMap::new(),
             outgoing: Vec::new(),
+            in_degree: Vec::new(),
         }
     }
-}
 
-fn parse_graph(data: &[String]) -> Option<HashMap<char, Node>> {
-    let mut v = Vec::new();
-    for line in data {
-        let line = line
-            .split(' ')
-            .map(|s| s.chars().take(1).next())
-            .collect::<Option<Vec<char>>>()?;
-        v.push((line[1], line[7]));
+    fn node(&mut self, label: K) -> usize {
+        if let Some(&i) = self.index.get(&label) {
+            return i;
+        }
+        let i = self.labels.len();
+        self.index.insert(label.clone(), i);
+        self.labels.push(label);
+        self.outgoing.push(Vec::new());
+        self.in_degree.push(0);
+        i
+    }
+
+    fn add_edge(&mut self, from: K, to: K) {
+        let (from, to) = (self.node(from), self.node(to));
+        if !self.outgoing[from].contains(&to) {
+            self.outgoing[from].push(to);
+            self.in_degree[to] += 1;
+        }
     }
-    let mut graph: HashMap<char, Node> = HashMap::new();
 
-    for (i, o) in v {
-        graph
-            .entry(i)
-            .or_insert_with(|| Node::new(i))
-            .outgoing
-            .push(o);
-        graph
-            .entry(o)
-            .or_insert_with(|| Node::new(o))
-            .incoming
-            .push(i);
+    fn len(&self) -> usize {
+        self.labels.len()
     }
-    Some(graph)
-}
 
-fn part1(data: &[String]) -> Option<String> {
-    let graph = parse_graph(data)?;
-    let mut steps: Vec<char> = Vec::new();
+    fn label(&self, node: usize) -> &K {
+        &self.labels[node]
+    }
 
-    // Hashset of ready steps
-    let mut ready: HashSet<char> = graph
-        .iter()
-        .filter_map(|(id, node)| {
-            if node.incoming.is_empty() {
-                Some(*id)
-            } else {
-                None
+    fn outgoing(&self, node: usize) -> &[usize] {
+        &self.outgoing[node]
+    }
+
+    fn roots(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len()).filter(move |&i| self.in_degree[i] == 0)
+    }
+
+    /// Kahn's algorithm, taking the ready node with the smallest `priority`
+    /// each time, in O((V + E) log V). Nodes blocked by a cycle are left out.
+    fn topological_sort<P: Ord>(&self, priority: impl Fn(usize) -> P) -> Vec<usize> {
+        let mut degree = self.in_degree.clone();
+        let mut ready = self
+            .roots()
+            .map(|i| Reverse((priority(i), i)))
+            .collect::<BinaryHeap<_>>();
+        let mut order = Vec::with_capacity(self.len());
+        while let Some(Reverse((_, node))) = ready.pop() {
+            for &child in self.outgoing(node) {
+                degree[child] -= 1;
+                if degree[child] == 0 {
+                    ready.push(Reverse((priority(child), child)));
+                }
             }
-        })
-        .collect();
-
-    while !ready.is_empty() {
-        // Alphabetically sort our list of ready steps
-        let mut available = ready.iter().cloned().collect::<Vec<char>>();
-        available.sort();
-        let a = available.get(0)?;
-        let node = graph.get(a)?;
-        for child_id in &node.outgoing {
-            if steps.contains(child_id) {
-                // we've already done this child
+            order.push(node);
+        }
+        order
+    }
+
+    /// Nodes that can never be started because a cycle leads to them. Every
+    /// other node runs out of unfinished predecessors in Kahn's algorithm.
+    fn blocked(&self) -> Vec<usize> {
+        let mut sorted = vec![false; self.len()];
+        for i in self.topological_sort(|i| i) {
+            sorted[i] = true;
+        }
+        (0..self.len()).filter(|&i| !sorted[i]).collect()
+    }
+
+    /// Some cycle, as a path whose last node leads back to the first
+    fn cycle(&self) -> Option<Vec<usize>> {
+        // 0: unvisited, 1: on the current path, 2: finished
+        let mut state = vec![0u8; self.len()];
+        for start in 0..self.len() {
+            if state[start] != 0 {
                 continue;
             }
-            // Check if any children are ready
-            if graph
-                .get(child_id)?
-                .incoming
-                .iter()
-                .filter(|id| !(steps.contains(id) || *id == a))
-                .count()
-                == 0
-            {
-                ready.insert(*child_id);
+            // Each entry is a node on the path and the next child to visit
+            let mut path = vec![(start, 0)];
+            state[start] = 1;
+            while let Some((node, next)) = path.last_mut() {
+                let node = *node;
+                match self.outgoing(node).get(*next) {
+                    Some(&child) => {
+                        *next += 1;
+                        match state[child] {
+                            0 => {
+                                state[child] = 1;
+                                path.push((child, 0));
+                            }
+                            1 => {
+                                let from = path.iter().position(|&(n, _)| n == child)?;
+                                return Some(path[from..].iter().map(|&(n, _)| n).collect());
+                            }
+                            _ => {}
+                        }
+                    }
+                    None => {
+                        state[node] = 2;
+                        path.pop();
+                    }
+                }
             }
         }
-        ready.remove(a);
-        steps.push(*a);
-        // Continue to the outer loop, because this child node becoming ready
-        // may allow other nodes that have alphabetic priority to become ready
+        None
+    }
+}
+
+impl<K: Ord> Dag<K> {
+    fn ranks(&self) -> Vec<usize> {
+        let mut sorted = (0..self.labels.len()).collect::<Vec<usize>>();
+        sorted.sort_by_key(|&i| &self.labels[i]);
+        let mut rank = vec![0; sorted.len()];
+        for (r, &i) in sorted.iter().enumerate() {
+            rank[i] = r;
+        }
+        rank
     }
-    Some(steps.iter().cloned().collect::<String>())
 }
 
-fn duration(letter: char, additional: u32) -> u32 {
-    (letter.to_ascii_uppercase() as u32 - 'A' as u32) + additional
+#[derive(Debug, PartialEq)]
+enum GraphError {
+    InvalidLine(usize),
+    InvalidLabel(usize),
+    /// `path` names one cycle and `unreachable` every other step waiting on one
+    Cycle {
+        path: String,
+        unreachable: Vec<String>,
+    },
+    NoWorkers,
+    Unassignable(String),
+    NoDuration(String),
+    Overflow,
 }
 
-fn part2(data: &[String], workers: usize, additional: u32) -> Option<u32> {
-    let graph = parse_graph(data)?;
+fn parse_edge(line: &str) -> Option<(&str, &str)> {
+    let rest = line.trim().strip_prefix("Step ")?;
+    let rest = rest.strip_suffix(" can begin.")?;
+    let mut parts = rest.split(" must be finished before step ");
+    let (before, after) = (parts.next()?, parts.next()?);
+    if parts.next().is_some() || before.is_empty() || after.is_empty() {
+        return None;
+    }
+    Some((before, after))
+}
 
-    let mut ready: HashSet<char> = graph
-        .iter()
-        .filter_map(|(id, node)| {
-            if node.incoming.is_empty() {
-                Some(*id)
-            } else {
-                None
-            }
+fn parse_graph<K: Clone + Eq + Hash + FromStr + ToString>(
+    data: &[String],
+) -> Result<Dag<K>, GraphError> {
+    let mut graph = Dag::new();
+    for (i, line) in data.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (before, after) = parse_edge(line).ok_or(GraphError::InvalidLine(i + 1))?;
+        let label = |s: &str| s.parse::<K>().map_err(|_| GraphError::InvalidLabel(i + 1));
+        graph.add_edge(label(before)?, label(after)?);
+    }
+    if let Some(cycle) = graph.cycle() {
+        let mut path = cycle
+            .iter()
+            .map(|&i| graph.label(i).to_string())
+            .collect::<Vec<String>>();
+        path.push(path[0].clone());
+        let mut unreachable = graph
+            .blocked()
+            .into_iter()
+            .filter(|i| !cycle.contains(i))
+            .map(|i| graph.label(i).to_string())
+            .collect::<Vec<String>>();
+        unreachable.sort();
+        return Err(GraphError::Cycle {
+            path: path.join(" -> "),
+            unreachable,
+        });
+    }
+    Ok(graph)
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+enum TieBreak {
+    #[default]
+    Alphabetical,
+    Reverse,
+    /// Shortest `duration` first, then alphabetical
+    Duration(u32),
+}
+
+fn part1(data: &[String], tie: TieBreak) -> Result<String, GraphError> {
+    let graph = parse_graph::<String>(data)?;
+    let rank = graph.ranks();
+    let n = graph.len();
+    let times = match tie {
+        TieBreak::Duration(additional) => durations(&graph, additional)?,
+        _ => vec![0; n],
+    };
+    let steps = graph.topological_sort(|i| match tie {
+        TieBreak::Reverse => (0, n - rank[i]),
+        _ => (times[i], rank[i]),
+    });
+    Ok(steps.iter().map(|&i| graph.label(i).as_str()).collect())
+}
+
+/// Position in the alphabet plus `additional`, for single letter labels only
+fn duration(step: &str, additional: u32) -> Option<u32> {
+    let mut chars = step.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphabetic() => {
+            Some(c.to_ascii_uppercase() as u32 - 'A' as u32 + 1 + additional)
+        }
+        _ => None,
+    }
+}
+
+fn durations(graph: &Dag<String>, additional: u32) -> Result<Vec<u32>, GraphError> {
+    (0..graph.len())
+        .map(|i| {
+            let label = graph.label(i);
+            duration(label, additional).ok_or_else(|| GraphError::NoDuration(label.clone()))
         })
-        .collect();
+        .collect()
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Task {
+    step: usize,
+    worker: usize,
+    start: u32,
+    end: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Schedule {
+    workers: usize,
+    tasks: Vec<Task>,
+}
+
+impl Schedule {
+    fn makespan(&self) -> u32 {
+        self.tasks.iter().map(|t| t.end).max().unwrap_or(0)
+    }
+
+    fn timeline(&self, worker: usize) -> impl Iterator<Item = &Task> + '_ {
+        self.tasks.iter().filter(move |t| t.worker == worker)
+    }
+}
+
+/// `speed` is a percentage of normal speed
+#[derive(Debug, Clone, PartialEq)]
+struct Worker {
+    speed: u32,
+    skills: Option<HashSet<usize>>,
+}
 
-    let mut remaining = graph.keys().cloned().collect::<HashSet<char>>();
-    let mut ticks = 0;
-    let mut done: Vec<char> = Vec::new();
-    let mut jobs = (0..=workers).map(|_| Job::default()).collect::<Vec<Job>>();
+impl Default for Worker {
+    fn default() -> Worker {
+        Worker {
+            speed: 100,
+            skills: None,
+        }
+    }
+}
+
+impl Worker {
+    fn can_do(&self, step: usize) -> bool {
+        self.speed > 0 && self.skills.as_ref().is_none_or(|s| s.contains(&step))
+    }
 
-    // Outer loop represents 1 second of work
-    let mut queue: VecDeque<char> = VecDeque::new();
+    fn time(&self, base: u32) -> Option<u32> {
+        let t = (base as u64 * 100).div_ceil(self.speed as u64);
+        u32::try_from(t).ok()
+    }
+}
+
+/// Whenever workers are idle, ready steps are handed out smallest `priority`
+/// first, each to the capable idle worker who would finish soonest. A step no
+/// idle worker can do waits, without holding up those behind it.
+fn simulate<K: Clone + Eq + Hash, P: Ord>(
+    graph: &Dag<K>,
+    workers: &[Worker],
+    time: impl Fn(usize) -> u32,
+    priority: impl Fn(usize) -> P,
+) -> Schedule {
+    let mut degree = graph.in_degree.clone();
+    let mut ready = graph
+        .roots()
+        .map(|i| Reverse((priority(i), i)))
+        .collect::<BinaryHeap<_>>();
+    let mut idle = (0..workers.len()).collect::<Vec<usize>>();
+    // Steps in progress, as (end, worker, step)
+    let mut running = BinaryHeap::new();
+    let mut tasks = Vec::with_capacity(graph.len());
+    let mut now = 0;
     loop {
-        // Loop through all our jobs twice, first to see if any have finished - which may open up
-        // new opportunities. There's an edge case on my input here, where the last worker
-        // in the queue frees up 3 possible tasks, but the next iteration skips them.
-        for job in jobs.iter_mut() {
-            if job.clock > 0 {
-                job.clock -= 1;
-            } else {
-                // Did we just finish a job?
-                if let Some(c) = job.letter.take() {
-                    // We had a job to work on, just finished
-                    let node = graph.get(&c)?;
-                    // Are any of our direct outgoing nodes now available to work on?
-                    for child_id in &node.outgoing {
-                        if done.contains(child_id) {
-                            // we've already done this child
-                            continue;
-                        }
-                        if graph
-                            .get(child_id)?
-                            .incoming
-                            .iter()
-                            .filter(|id| !(done.contains(id) || *id == &c))
-                            .count()
-                            == 0
-                        {
-                            ready.insert(*child_id);
-                        }
-                    }
-                    done.push(c);
-                    remaining.remove(&c);
-                    job.letter = None;
+        let mut waiting = Vec::new();
+        while !idle.is_empty() {
+            let Reverse((p, step)) = match ready.pop() {
+                Some(next) => next,
+                None => break,
+            };
+            let best = idle
+                .iter()
+                .enumerate()
+                .filter(|&(_, &w)| workers[w].can_do(step))
+                .filter_map(|(slot, &w)| Some((workers[w].time(time(step))?, w, slot)))
+                .min();
+            let (worker, end) = match best {
+                Some((t, _, slot)) => (idle.swap_remove(slot), now + t),
+                None => {
+                    waiting.push(Reverse((p, step)));
+                    continue;
+                }
+            };
+            tasks.push(Task {
+                step,
+                worker,
+                start: now,
+                end,
+            });
+            running.push(Reverse((end, worker, step)));
+        }
+        ready.extend(waiting);
+
+        now = match running.peek() {
+            Some(&Reverse((end, _, _))) => end,
+            None => break,
+        };
+        while let Some(&Reverse((end, worker, step))) = running.peek() {
+            if end > now {
+                break;
+            }
+            running.pop();
+            idle.push(worker);
+            for &child in graph.outgoing(step) {
+                degree[child] -= 1;
+                if degree[child] == 0 {
+                    ready.push(Reverse((priority(child), child)));
                 }
             }
         }
+    }
+    Schedule {
+        workers: workers.len(),
+        tasks,
+    }
+}
+
+fn schedule<K: Clone + Ord + Hash>(
+    graph: &Dag<K>,
+    workers: usize,
+    time: impl Fn(usize) -> u32,
+) -> Schedule {
+    let rank = graph.ranks();
+    simulate(graph, &vec![Worker::default(); workers], time, |i| rank[i])
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Timing {
+    earliest: u32,
+    latest: u32,
+}
+
+impl Timing {
+    fn slack(&self) -> u32 {
+        self.latest - self.earliest
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct CriticalPath {
+    timings: Vec<Timing>,
+    /// A lower bound on the makespan for any number of workers
+    length: u32,
+    path: Vec<usize>,
+}
 
-        // There are ready jobs that need to be added to the queue.
-        if !ready.is_empty() {
-            let mut available = ready.iter().cloned().collect::<Vec<char>>();
-            available.sort_by(|&a, &b| duration(a, additional).cmp(&duration(b, additional)));
-            queue.extend(VecDeque::from(available));
-            ready.clear();
+fn critical_path<K: Clone + Ord + Hash>(
+    graph: &Dag<K>,
+    time: impl Fn(usize) -> u32,
+) -> CriticalPath {
+    let rank = graph.ranks();
+    let order = graph.topological_sort(|i| rank[i]);
+    let mut earliest = vec![0; graph.len()];
+    for &node in &order {
+        for &child in graph.outgoing(node) {
+            earliest[child] = earliest[child].max(earliest[node] + time(node));
         }
+    }
+    let length = order
+        .iter()
+        .map(|&i| earliest[i] + time(i))
+        .max()
+        .unwrap_or(0);
+    let mut latest = vec![0; graph.len()];
+    for &node in order.iter().rev() {
+        let finish = graph
+            .outgoing(node)
+            .iter()
+            .map(|&child| latest[child])
+            .min()
+            .unwrap_or(length);
+        latest[node] = finish - time(node);
+    }
+    let timings = (0..graph.len())
+        .map(|i| Timing {
+            earliest: earliest[i],
+            latest: latest[i],
+        })
+        .collect::<Vec<Timing>>();
+
+    // Follow critical steps whose start is exactly when the previous ends
+    let critical = |i: usize| timings[i].slack() == 0;
+    let mut path = Vec::new();
+    let mut next = graph
+        .roots()
+        .filter(|&i| critical(i))
+        .min_by_key(|&i| rank[i]);
+    while let Some(node) = next {
+        path.push(node);
+        next = graph
+            .outgoing(node)
+            .iter()
+            .cloned()
+            .filter(|&c| critical(c) && earliest[c] == earliest[node] + time(node))
+            .min_by_key(|&c| rank[c]);
+    }
+    CriticalPath {
+        timings,
+        length,
+        path,
+    }
+}
+
+/// Schedule for a mix of workers, aiming for the shortest makespan. This is
+/// greedy list scheduling by highest level first: among ready steps, the one
+/// with the longest chain of work still behind it, itself included, is
+/// handed out first, alphabetically on a tie, to whichever capable idle
+/// worker finishes it soonest. Idle workers are never held back for a
+/// better step later, so the result is not always optimal.
+fn plan<K: Clone + Ord + Hash + ToString>(
+    graph: &Dag<K>,
+    workers: &[Worker],
+    time: impl Fn(usize) -> u32,
+) -> Result<Schedule, GraphError> {
+    if let Some(step) = (0..graph.len()).find(|&i| !workers.iter().any(|w| w.can_do(i))) {
+        return Err(GraphError::Unassignable(graph.label(step).to_string()));
+    }
+    // Some step is always in progress until the end, so the makespan is at
+    // most every step done by its slowest capable worker
+    let mut slowest = 0u64;
+    let mut total = 0u64;
+    for i in 0..graph.len() {
+        let base = time(i);
+        let t = workers
+            .iter()
+            .filter(|w| w.can_do(i))
+            .try_fold(0, |slowest, w| Some(slowest.max(w.time(base)?)))
+            .ok_or(GraphError::Overflow)?;
+        slowest += t as u64;
+        total += base as u64;
+    }
+    if slowest.max(total) > u32::MAX as u64 {
+        return Err(GraphError::Overflow);
+    }
+    let analysis = critical_path(graph, &time);
+    let rank = graph.ranks();
+    Ok(simulate(graph, workers, &time, |i| {
+        (analysis.timings[i].latest, rank[i])
+    }))
+}
+
+/// Parse `speed[:step,step...]`, a speed in percent and optionally the only
+/// steps the worker can do. Unknown steps are an error.
+fn parse_worker(spec: &str, graph: &Dag<String>) -> Option<Worker> {
+    let mut parts = spec.splitn(2, ':');
+    let speed = parts.next()?.parse::<u32>().ok()?;
+    let skills = match parts.next() {
+        Some(steps) => Some(
+            steps
+                .split(',')
+                .map(|s| graph.index.get(s).cloned())
+                .collect::<Option<HashSet<usize>>>()?,
+        ),
+        None => None,
+    };
+    Some(Worker { speed, skills })
+}
+
+fn parse_durations(data: &[String]) -> Result<HashMap<String, u32>, GraphError> {
+    let mut table = HashMap::new();
+    for (i, line) in data.iter().enumerate() {
+        let mut parts = line.split_whitespace();
+        let (step, seconds) = match (parts.next(), parts.next(), parts.next()) {
+            (None, _, _) => continue,
+            (Some(step), Some(seconds), None) => (step, seconds),
+            _ => return Err(GraphError::InvalidLine(i + 1)),
+        };
+        let seconds = seconds
+            .parse::<u32>()
+            .map_err(|_| GraphError::InvalidLine(i + 1))?;
+        table.insert(step.to_string(), seconds);
+    }
+    Ok(table)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Export {
+    Text,
+    Csv,
+    Svg,
+}
+
+fn export<K: Clone + Eq + Hash + ToString>(
+    graph: &Dag<K>,
+    plan: &Schedule,
+    format: Export,
+) -> String {
+    let label = |t: &Task| graph.label(t.step).to_string();
+    match format {
+        Export::Text => {
+            let width = plan
+                .tasks
+                .iter()
+                .map(|t| label(t).len())
+                .chain(Some(format!("Worker {}", plan.workers).len()))
+                .max()
+                .unwrap_or(0);
+            let mut done = plan.tasks.clone();
+            done.sort_by_key(|t| t.end);
 
-        for job in jobs.iter_mut() {
-            // Take a job if we don't have one.
-            if job.letter.is_none() {
-                job.letter = queue.pop_front();
-                if let Some(c) = job.letter {
-                    job.clock = duration(c, additional);
-                    ready.remove(&c);
+            let mut out = String::from("Second");
+            for w in 0..plan.workers {
+                out += &format!("   {:^width$}", format!("Worker {}", w + 1), width = width);
+            }
+            out += "   Done\n";
+            for second in 0..=plan.makespan() {
+                let mut row = format!("{:^6}", second);
+                for w in 0..plan.workers {
+                    let step = plan
+                        .timeline(w)
+                        .find(|t| t.start <= second && second < t.end)
+                        .map_or(String::from("."), label);
+                    row += &format!("   {:^width$}", step, width = width);
+                }
+                row += "   ";
+                for t in done.iter().take_while(|t| t.end <= second) {
+                    row += &label(t);
                 }
+                out += row.trim_end();
+                out += "\n";
             }
+            out
         }
-
-        // println!(
-        //     "{:04} {:?} {}",
-        //     ticks,
-        //     jobs.iter()
-        //         .map(|j| j.letter.unwrap_or('.'))
-        //         .collect::<Vec<_>>(),
-        //     done.iter().cloned().collect::<String>()
-        // );
-
-        if remaining.is_empty() {
-            break;
+        Export::Csv => {
+            let mut out = String::from("step,worker,start,end\n");
+            for t in &plan.tasks {
+                let mut step = label(t);
+                if step.contains([',', '"', '\n']) {
+                    step = format!("\"{}\"", step.replace('"', "\"\""));
+                }
+                out += &format!("{},{},{},{}\n", step, t.worker + 1, t.start, t.end);
+            }
+            out
+        }
+        Export::Svg => {
+            // Bars are scaled to a fixed width, with worker names in a left
+            // margin and a row of `ROW` units per worker
+            const ROW: f64 = 24.0;
+            const MARGIN: f64 = 80.0;
+            const WIDTH: f64 = 800.0;
+            let scale = WIDTH / plan.makespan().max(1) as f64;
+            let height = ROW * (plan.workers + 1) as f64;
+            let mut out = format!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+                 font-family=\"monospace\" font-size=\"12\">\n",
+                MARGIN + WIDTH + 10.0,
+                height
+            );
+            for w in 0..plan.workers {
+                out += &format!(
+                    "<text x=\"4\" y=\"{}\">Worker {}</text>\n",
+                    ROW * w as f64 + 16.0,
+                    w + 1
+                );
+            }
+            for t in &plan.tasks {
+                let (x, y) = (MARGIN + t.start as f64 * scale, ROW * t.worker as f64 + 2.0);
+                let w = (t.end - t.start) as f64 * scale;
+                out += &format!(
+                    "<g><title>{} on worker {}: {}-{}</title>\
+                     <rect x=\"{:.1}\" y=\"{}\" width=\"{:.1}\" height=\"{}\" \
+                     fill=\"steelblue\" stroke=\"white\"/>\
+                     <text x=\"{:.1}\" y=\"{}\" fill=\"white\">{}</text></g>\n",
+                    label(t),
+                    t.worker + 1,
+                    t.start,
+                    t.end,
+                    x,
+                    y,
+                    w,
+                    ROW - 4.0,
+                    x + 2.0,
+                    y + 14.0,
+                    label(t)
+                );
+            }
+            // Time axis along the bottom, with the makespan at the end
+            let axis = ROW * plan.workers as f64 + 14.0;
+            out += &format!(
+                "<text x=\"{}\" y=\"{}\">0</text>\
+                 <text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>\n",
+                MARGIN,
+                axis,
+                MARGIN + WIDTH,
+                axis,
+                plan.makespan()
+            );
+            out += "</svg>\n";
+            out
         }
-        ticks += 1;
     }
+}
 
-    Some(ticks)
+fn part2(data: &[String], workers: usize, additional: u32) -> Result<u32, GraphError> {
+    let graph = parse_graph::<String>(data)?;
+    if workers == 0 && graph.len() > 0 {
+        return Err(GraphError::NoWorkers);
+    }
+    let times = durations(&graph, additional)?;
+    Ok(schedule(&graph, workers, |i| times[i]).makespan())
 }
 
 #[test]
 fn part1_test() {
     let data = util::read_lines("test1.txt").unwrap();
-    assert_eq!(part1(&data), Some(String::from("CABDFE")));
+    assert_eq!(
+        part1(&data, TieBreak::Alphabetical),
+        Ok(String::from("CABDFE"))
+    );
+    assert_eq!(part1(&data, TieBreak::Reverse), Ok(String::from("CFADBE")));
+    assert_eq!(
+        part1(&data, TieBreak::Duration(60)),
+        Ok(String::from("CABDFE"))
+    );
 }
 
 #[test]
 fn part2_test() {
     let data = util::read_lines("test1.txt").unwrap();
-    assert_eq!(part2(&data, 1, 0), Some(15));
+    assert_eq!(part2(&data, 2, 0), Ok(15));
+    assert_eq!(part2(&data, 1, 0), Ok(21));
+    assert_eq!(part2(&data, 0, 0), Err(GraphError::NoWorkers));
+
+    let graph = parse_graph::<String>(&data).unwrap();
+    let times = durations(&graph, 0).unwrap();
+    let plan = schedule(&graph, 2, |i| times[i]);
+    let steps = |w| {
+        plan.timeline(w)
+            .map(|t| graph.label(t.step).as_str())
+            .collect::<String>()
+    };
+    assert_eq!(
+        (steps(0), steps(1)),
+        (String::from("CABDE"), String::from("F"))
+    );
+    let f = plan.timeline(1).next().unwrap();
+    assert_eq!((f.start, f.end), (3, 9));
+}
+
+#[test]
+fn export_test() {
+    let data = util::read_lines("test1.txt").unwrap();
+    let graph = parse_graph::<String>(&data).unwrap();
+    let times = durations(&graph, 0).unwrap();
+    let plan = schedule(&graph, 2, |i| times[i]);
+
+    let text = export(&graph, &plan, Export::Text);
+    let lines = text.lines().collect::<Vec<&str>>();
+    assert_eq!(lines[0], "Second   Worker 1   Worker 2   Done");
+    assert_eq!(lines[1], "  0         C          .");
+    assert_eq!(lines[4], "  3         A          F       C");
+    assert_eq!(lines[16], "  15        .          .       CABFDE");
+
+    let csv = export(&graph, &plan, Export::Csv);
+    assert_eq!(csv.lines().nth(3), Some("F,2,3,9"));
+    let svg = export(&graph, &plan, Export::Svg);
+    assert_eq!(svg.matches("<rect").count(), 6);
+}
+
+#[test]
+fn critical_path_test() {
+    let data = util::read_lines("test1.txt").unwrap();
+    let graph = parse_graph::<String>(&data).unwrap();
+    let times = durations(&graph, 0).unwrap();
+    let analysis = critical_path(&graph, |i| times[i]);
+    let path = analysis
+        .path
+        .iter()
+        .map(|&i| graph.label(i).as_str())
+        .collect::<String>();
+    assert_eq!((path.as_str(), analysis.length), ("CFE", 14));
+
+    // A can start at 3 but waits until 4 without delaying E
+    let a = analysis.timings[graph.index["A"]];
+    assert_eq!((a.earliest, a.latest, a.slack()), (3, 4, 1));
+    assert_eq!(analysis.timings[graph.index["B"]].slack(), 3);
+    // Two workers cannot beat unlimited ones
+    assert!(part2(&data, 2, 0).unwrap() >= analysis.length);
+}
+
+#[test]
+fn plan_test() {
+    let data = util::read_lines("test1.txt").unwrap();
+    let graph = parse_graph::<String>(&data).unwrap();
+    let table = util::lines(&["C 1", "A 1", "B 1", "D 4", "", "E 1", "F 3"]);
+    let table = parse_durations(&table).unwrap();
+    let time = |i: usize| table[graph.label(i)];
+
+    // When A is done, alphabetical order picks B over D, which has more
+    // work behind it and so goes first with highest level first
+    assert_eq!(schedule(&graph, 2, time).makespan(), 8);
+    let two = vec![Worker::default(); 2];
+    assert_eq!(plan(&graph, &two, time).unwrap().makespan(), 7);
+
+    let f = graph.index["F"];
+    let workers = vec![
+        Worker {
+            speed: 200,
+            skills: Some((0..graph.len()).filter(|&i| i != f).collect()),
+        },
+        Worker {
+            speed: 50,
+            skills: None,
+        },
+    ];
+    let mixed = plan(&graph, &workers, time).unwrap();
+    let task = mixed.tasks.iter().find(|t| t.step == f).unwrap();
+    assert_eq!((task.worker, task.end - task.start), (1, 6));
+    assert_eq!(mixed.makespan(), 8);
+
+    let nobody = vec![Worker {
+        speed: 100,
+        skills: Some(HashSet::new()),
+    }];
+    assert_eq!(
+        plan(&graph, &nobody, time).err(),
+        Some(GraphError::Unassignable(String::from("C")))
+    );
+
+    let long = plan(&graph, &two, |_| 50_000_000).unwrap();
+    assert_eq!(long.makespan(), 200_000_000);
+    assert_eq!(
+        plan(&graph, &two, |_| u32::MAX / 4).err(),
+        Some(GraphError::Overflow)
+    );
+    let slow = vec![Worker {
+        speed: 50,
+        skills: None,
+    }];
+    assert_eq!(slow[0].time(3_000_000_000), None);
+
+    assert_eq!(
+        parse_worker("200", &graph),
+        Some(Worker {
+            speed: 200,
+            skills: None
+        })
+    );
+    let skilled = parse_worker("50:A,F", &graph).unwrap().skills.unwrap();
+    assert!(skilled.contains(&graph.index["A"]) && skilled.contains(&f));
+    assert_eq!(parse_worker("50:A,G", &graph), None);
+    assert_eq!(
+        plan(&graph, &slow, |_| 3_000_000_000).err(),
+        Some(GraphError::Overflow)
+    );
+}
+
+#[test]
+fn parse_graph_test() {
+    let data = util::lines(&[
+        "Step Mix must be finished before step Bake can begin.",
+        "Step Preheat must be finished before step Bake can begin.",
+        "Step Bake must be finished before step Serve can begin.",
+    ]);
+    let graph = parse_graph::<String>(&data).unwrap();
+    assert_eq!(graph.len(), 4);
+    let bake = graph.index["Bake"];
+    assert_eq!(graph.in_degree[bake], 2);
+    assert_eq!(graph.label(graph.outgoing(bake)[0]), "Serve");
+    assert_eq!(
+        part1(&data, TieBreak::default()),
+        Ok(String::from("MixPreheatBakeServe"))
+    );
+    assert_eq!((duration("a", 0), duration("Z", 60)), (Some(1), Some(86)));
+    assert_eq!((duration("Mix", 0), duration("10", 0)), (None, None));
+    assert_eq!(
+        part2(&data, 2, 0),
+        Err(GraphError::NoDuration(String::from("Mix")))
+    );
+
+    let data = vec![String::from(
+        "Step 10 must be finished before step 2 can begin.",
+    )];
+    let graph = parse_graph::<u32>(&data).unwrap();
+    assert_eq!(
+        graph.roots().map(|i| *graph.label(i)).collect::<Vec<u32>>(),
+        vec![10]
+    );
+    assert_eq!(
+        parse_graph::<u32>(&[String::from(
+            "Step A must be finished before step 2 can begin."
+        )])
+        .err(),
+        Some(GraphError::InvalidLabel(1))
+    );
+    assert_eq!(
+        parse_graph::<String>(&[String::from("Step A then B")]).err(),
+        Some(GraphError::InvalidLine(1))
+    );
+}
+
+#[test]
+fn cycle_test() {
+    let mut data = util::read_lines("test1.txt").unwrap();
+    data.push(String::from(
+        "Step E must be finished before step C can begin.",
+    ));
+    let cycle = Err(GraphError::Cycle {
+        path: String::from("C -> A -> B -> E -> C"),
+        unreachable: vec![String::from("D"), String::from("F")],
+    });
+    assert_eq!(part1(&data, TieBreak::default()), cycle);
+    assert_eq!(part2(&data, 2, 0).err(), cycle.err());
 }
 
 fn main() -> io::Result<()> {
     let data = util::read_lines("input.txt")?;
-    println!("Part 1: {:?}", part1(&data));
+    let mut args = std::env::args().skip(1);
+    let invalid = |_| io::Error::from(io::ErrorKind::InvalidData);
+    // Optional tie-break for part 1, or a part 2 schedule to print:
+    // `day07 [alphabetical|reverse|duration]`, `day07 timeline`,
+    // `day07 critical`, `day07 plan <durations> [workers]` or
+    // `day07 schedule <text|csv|svg> [path]`
+    let tie = match args.next().as_deref() {
+        None | Some("alphabetical") => TieBreak::Alphabetical,
+        Some("reverse") => TieBreak::Reverse,
+        Some("duration") => TieBreak::Duration(60),
+        Some("plan") => {
+            // `day07 plan <durations> [speed[:step,step...]...]`, with
+            // steps missing from the table taking their usual time
+            let graph = parse_graph::<String>(&data).map_err(invalid)?;
+            let path = args
+                .next()
+                .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?;
+            let table = parse_durations(&util::read_lines(path)?).map_err(invalid)?;
+            if table.keys().any(|step| !graph.index.contains_key(step)) {
+                return Err(io::Error::from(io::ErrorKind::InvalidInput));
+            }
+            let mut workers = args
+                .map(|spec| parse_worker(&spec, &graph))
+                .collect::<Option<Vec<Worker>>>()
+                .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?;
+            if workers.is_empty() {
+                workers = vec![Worker::default(); 5];
+            }
+            let times = (0..graph.len())
+                .map(|i| {
+                    let label = graph.label(i);
+                    table
+                        .get(label)
+                        .cloned()
+                        .or_else(|| duration(label, 60))
+                        .ok_or_else(|| GraphError::NoDuration(label.clone()))
+                })
+                .collect::<Result<Vec<u32>, GraphError>>();
+            let mixed = times
+                .and_then(|times| plan(&graph, &workers, |i| times[i]))
+                .map_err(invalid)?;
+            print!("{}", export(&graph, &mixed, Export::Text));
+            println!("Makespan: {}", mixed.makespan());
+            return Ok(());
+        }
+        Some(mode) => {
+            let graph = parse_graph::<String>(&data).map_err(invalid)?;
+            let times = durations(&graph, 60).map_err(invalid)?;
+            let standard = schedule(&graph, 5, |i| times[i]);
+            let format = match (mode, args.next().as_deref()) {
+                ("timeline", None) => {
+                    for w in 0..standard.workers {
+                        let steps = standard
+                            .timeline(w)
+                            .map(|t| format!("{} {}-{}", graph.label(t.step), t.start, t.end))
+                            .collect::<Vec<String>>();
+                        println!("Worker {}: {}", w + 1, steps.join(", "));
+                    }
+                    return Ok(());
+                }
+                ("critical", None) => {
+                    let analysis = critical_path(&graph, |i| times[i]);
+                    println!("Step  Earliest  Latest  Slack");
+                    for i in graph.topological_sort(|i| analysis.timings[i].earliest) {
+                        let t = analysis.timings[i];
+                        println!(
+                            "{:>4}  {:>8}  {:>6}  {:>5}",
+                            graph.label(i),
+                            t.earliest,
+                            t.latest,
+                            t.slack()
+                        );
+                    }
+                    let path = analysis
+                        .path
+                        .iter()
+                        .map(|&i| graph.label(i).as_str())
+                        .collect::<Vec<&str>>();
+                    println!("Critical path: {}", path.join(" -> "));
+                    println!("Unlimited workers: {}", analysis.length);
+                    return Ok(());
+                }
+                ("schedule", Some("text")) => Export::Text,
+                ("schedule", Some("csv")) => Export::Csv,
+                ("schedule", Some("svg")) => Export::Svg,
+                _ => return Err(io::Error::from(io::ErrorKind::InvalidInput)),
+            };
+            let out = export(&graph, &standard, format);
+            match args.next() {
+                Some(path) => std::fs::write(path, out)?,
+                None => print!("{}", out),
+            }
+            return Ok(());
+        }
+    };
+    println!("Part 1: {:?}", part1(&data, tie));
     println!("Part 2: {:?}", part2(&data, 5, 60));
     Ok(())
 }
@@ -16,3 +16,14 @@ pub fn read_lines<P: AsRef<Path>>(path: P) -> io::Result<Vec<String>> {
     let buf = BufReader::new(f);
     buf.lines().collect::<io::Result<Vec<String>>>()
 }
+
+/// Binary PPM (P6) image from packed 8-bit RGB pixels, row by row
+pub fn ppm(width: usize, height: usize, pixels: &[u8]) -> Vec<u8> {
+    let mut out = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    out.extend_from_slice(pixels);
+    out
+}
+
+pub fn lines(data: &[&str]) -> Vec<String> {
+    data.iter().map(|s| s.to_string()).collect()
+}